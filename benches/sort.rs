@@ -0,0 +1,67 @@
+//! Compares the sorting engines with `slice::sort_unstable`.
+//!
+//! Run with `cargo bench`. Every engine sorts the same inputs, and the best
+//! time of a few runs is reported in milliseconds.
+
+use quicksort_gen::{Algorithm, Sorter};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::time::{Duration, Instant};
+
+const LEN: usize = 2_000_000;
+const RUNS: usize = 5;
+
+/// Returns the best time of sorting a fresh copy of `input` with `sort`.
+fn measure<F: Fn(&mut [i64])>(input: &[i64], sort: F) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let mut numbers = input.to_vec();
+            let start = Instant::now();
+            sort(&mut numbers);
+            let elapsed = start.elapsed();
+            assert!(numbers.windows(2).all(|w| w[0] <= w[1]));
+            elapsed
+        })
+        .min()
+        .unwrap()
+}
+
+/// Prints the best times of `sort` on every input in one row.
+fn report<F: Fn(&mut [i64])>(name: &str, inputs: &[(&str, Vec<i64>)], sort: F) {
+    print!("{:>14}", name);
+    for (_, input) in inputs.iter() {
+        let time = measure(input, &sort);
+        print!("{:>12.1}", time.as_secs_f64() * 1_000.0);
+    }
+    println!();
+}
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(42);
+    let random: Vec<i64> = (0..LEN).map(|_| rng.gen()).collect();
+    let inputs = [
+        ("random", random.clone()),
+        ("few unique", random.iter().map(|n| n % 16).collect()),
+        ("sorted", (0..LEN as i64).collect()),
+        ("reversed", (0..LEN as i64).rev().collect()),
+    ];
+
+    print!("{:>14}", "");
+    for (name, _) in inputs.iter() {
+        print!("{:>12}", name);
+    }
+    println!();
+
+    report("sort_unstable", &inputs, |arr| arr.sort_unstable());
+    let algorithms = [
+        ("pdq", Algorithm::Pdq),
+        ("quicksort", Algorithm::Quicksort),
+        ("dual pivot", Algorithm::DualPivot),
+    ];
+    for &(name, algorithm) in algorithms.iter() {
+        let sorter = Sorter::new().algorithm(algorithm);
+        report(name, &inputs, |arr| {
+            sorter.sort(arr);
+        });
+    }
+}
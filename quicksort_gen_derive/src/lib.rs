@@ -0,0 +1,480 @@
+//! Derive macros for the `Comparator` and `Copier` traits of `quicksort_gen`.
+//!
+//! Use them through the `derive` feature of `quicksort_gen`, which re-exports
+//! them next to the traits:
+//!
+//! ```
+//! use quicksort_gen::{Comparator, Copier};
+//!
+//! #[derive(Comparator, Copier)]
+//! struct Node {
+//!     #[compare(order = 1)]
+//!     pid: u64,
+//!     #[compare(order = 2, desc)]
+//!     name: String,
+//!     #[compare(skip)]
+//!     cpu_time: f64,
+//! }
+//! ```
+//!
+//! `#[derive(Comparator)]` compares structs field by field, lexicographically.
+//! Fields are compared in declaration order unless they carry an
+//! `#[compare(order = n)]` attribute: fields with an order come first, by
+//! increasing order, followed by the others. `desc` reverses the comparison of
+//! a field and `skip` leaves the field out of the comparison. Every compared
+//! field must implement `Comparator`, and the derived comparator returns
+//! `Ordering::Equal` exactly when all compared fields are equal.
+//!
+//! `#[derive(Copier)]` copies every field with `Copier::copy`.
+//!
+//! Type parameters get the derived trait as a bound when the type of a field
+//! taking part mentions them, so a type parameter used only by skipped fields
+//! need not implement `Comparator`.
+//!
+//! Every type which is `Ord` already implements `Comparator`, and every type
+//! which is `Clone` already implements `Copier`, so deriving both on the same
+//! type gives conflicting implementations. Derive `Comparator` only on types
+//! which are not `Ord`:
+//!
+//! ```compile_fail,E0119
+//! use quicksort_gen::Comparator;
+//!
+//! #[derive(Comparator, PartialEq, Eq, PartialOrd, Ord)]
+//! struct Pid(u64);
+//! ```
+//!
+//! and `Copier` only on types which are not `Clone`:
+//!
+//! ```compile_fail,E0119
+//! use quicksort_gen::Copier;
+//!
+//! #[derive(Clone, Copier)]
+//! struct Pid(u64);
+//! ```
+//!
+//! Only structs are supported. The macros are written against the bare
+//! `proc_macro` API so the crate has no dependencies.
+
+use proc_macro::{Delimiter, Group, Spacing, TokenStream, TokenTree};
+use std::iter::FromIterator;
+
+/// Derives `Comparator` for a struct, see the crate documentation.
+#[proc_macro_derive(Comparator, attributes(compare))]
+pub fn derive_comparator(input: TokenStream) -> TokenStream {
+    expand(input, comparator_impl)
+}
+
+/// Derives `Copier` for a struct, see the crate documentation.
+#[proc_macro_derive(Copier, attributes(compare))]
+pub fn derive_copier(input: TokenStream) -> TokenStream {
+    expand(input, copier_impl)
+}
+
+fn expand(input: TokenStream, generate: fn(&Struct) -> Result<String, String>) -> TokenStream {
+    let code = parse_struct(input)
+        .and_then(|item| generate(&item))
+        .unwrap_or_else(|message| format!("compile_error!({:?});", message));
+    code.parse().expect("derive generated invalid tokens")
+}
+
+//////////////////////////////////////////////////////////////////////////////
+// Parsing
+
+/// The parts of a struct definition needed to implement a trait for it.
+struct Struct {
+    name: String,
+    /// Generic parameters without their defaults, e.g. `'a, T: Clone`.
+    impl_generics: Vec<String>,
+    /// Generic arguments naming the parameters, e.g. `'a, T`.
+    type_generics: Vec<String>,
+    /// Type parameters, which get the derived trait as a bound if the fields
+    /// it is derived from mention them.
+    type_params: Vec<String>,
+    /// Predicates of the where clause of the struct.
+    predicates: Vec<String>,
+    fields: Fields,
+}
+
+enum Fields {
+    Named(Vec<Field>),
+    Unnamed(Vec<Field>),
+    Unit,
+}
+
+struct Field {
+    /// The field name, or its index for tuple structs.
+    member: String,
+    ty: TokenStream,
+    order: Option<i64>,
+    desc: bool,
+    skip: bool,
+}
+
+impl Fields {
+    fn list(&self) -> &[Field] {
+        match self {
+            Fields::Named(fields) | Fields::Unnamed(fields) => fields,
+            Fields::Unit => &[],
+        }
+    }
+}
+
+fn is_punct(token: &TokenTree, ch: char) -> bool {
+    matches!(token, TokenTree::Punct(punct) if punct.as_char() == ch)
+}
+
+fn is_ident(token: &TokenTree, name: &str) -> bool {
+    matches!(token, TokenTree::Ident(ident) if ident.to_string() == name)
+}
+
+fn to_string(tokens: &[TokenTree]) -> String {
+    TokenStream::from_iter(tokens.iter().cloned()).to_string()
+}
+
+/// Splits `tokens` at `separator` punctuation which is not nested inside angle
+/// brackets. Parentheses, brackets and braces are already nested as groups.
+fn split_top_level(tokens: &[TokenTree], separator: char) -> Vec<&[TokenTree]> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (idx, token) in tokens.iter().enumerate() {
+        if let TokenTree::Punct(punct) = token {
+            match punct.as_char() {
+                '<' => depth += 1,
+                // The `>` of `->` does not close an angle bracket.
+                '>' if !is_arrow(tokens, idx) => depth = depth.saturating_sub(1),
+                ch if ch == separator && depth == 0 => {
+                    parts.push(&tokens[start..idx]);
+                    start = idx + 1;
+                }
+                _ => {}
+            }
+        }
+    }
+    if start < tokens.len() {
+        parts.push(&tokens[start..]);
+    }
+    parts
+}
+
+fn is_arrow(tokens: &[TokenTree], idx: usize) -> bool {
+    idx > 0
+        && matches!(&tokens[idx - 1], TokenTree::Punct(punct)
+            if punct.as_char() == '-' && punct.spacing() == Spacing::Joint)
+}
+
+/// Skips outer attributes and a visibility qualifier at the start of
+/// `tokens`, returning the attributes and the remaining tokens.
+fn strip_attributes(tokens: &[TokenTree]) -> (Vec<&Group>, &[TokenTree]) {
+    let mut attributes = Vec::new();
+    let mut rest = tokens;
+    while let [pound, TokenTree::Group(group), tail @ ..] = rest {
+        if !is_punct(pound, '#') || group.delimiter() != Delimiter::Bracket {
+            break;
+        }
+        attributes.push(group);
+        rest = tail;
+    }
+    if let [vis, tail @ ..] = rest {
+        if is_ident(vis, "pub") {
+            rest = match tail {
+                [TokenTree::Group(group), tail @ ..]
+                    if group.delimiter() == Delimiter::Parenthesis =>
+                {
+                    tail
+                }
+                _ => tail,
+            };
+        }
+    }
+    (attributes, rest)
+}
+
+fn parse_struct(input: TokenStream) -> Result<Struct, String> {
+    let tokens: Vec<TokenTree> = input.into_iter().collect();
+    let (_, rest) = strip_attributes(&tokens);
+
+    let rest = match rest {
+        [keyword, rest @ ..] if is_ident(keyword, "struct") => rest,
+        _ => {
+            return Err(String::from(
+                "Comparator and Copier can only be derived for structs",
+            ))
+        }
+    };
+    let (name, mut rest) = match rest {
+        [TokenTree::Ident(name), rest @ ..] => (name.to_string(), rest),
+        _ => return Err(String::from("expected a struct name")),
+    };
+
+    let mut item = Struct {
+        name,
+        impl_generics: Vec::new(),
+        type_generics: Vec::new(),
+        type_params: Vec::new(),
+        predicates: Vec::new(),
+        fields: Fields::Unit,
+    };
+
+    if rest.first().is_some_and(|token| is_punct(token, '<')) {
+        let end = closing_angle(rest).ok_or("unterminated generic parameters")?;
+        parse_generics(&rest[1..end], &mut item);
+        rest = &rest[end + 1..];
+    }
+
+    // Named structs have their where clause before the fields, tuple structs
+    // after them.
+    let mut where_clause: &[TokenTree] = &[];
+    let mut body = None;
+    let mut idx = 0;
+    while idx < rest.len() {
+        match &rest[idx] {
+            token if is_ident(token, "where") => {
+                let end = rest[idx + 1..]
+                    .iter()
+                    .position(|token| {
+                        is_punct(token, ';')
+                            || matches!(token, TokenTree::Group(group)
+                                if group.delimiter() == Delimiter::Brace)
+                    })
+                    .map_or(rest.len(), |pos| idx + 1 + pos);
+                where_clause = &rest[idx + 1..end];
+                idx = end;
+            }
+            TokenTree::Group(group) if body.is_none() => {
+                body = Some(group.clone());
+                idx += 1;
+            }
+            _ => idx += 1,
+        }
+    }
+    item.predicates.extend(
+        split_top_level(where_clause, ',')
+            .into_iter()
+            .filter(|predicate| !predicate.is_empty())
+            .map(to_string),
+    );
+
+    item.fields = match body {
+        Some(group) if group.delimiter() == Delimiter::Brace => {
+            Fields::Named(parse_fields(group.stream(), true)?)
+        }
+        Some(group) if group.delimiter() == Delimiter::Parenthesis => {
+            Fields::Unnamed(parse_fields(group.stream(), false)?)
+        }
+        _ => Fields::Unit,
+    };
+    Ok(item)
+}
+
+/// Returns the index of the `>` closing the `<` at the start of `tokens`.
+fn closing_angle(tokens: &[TokenTree]) -> Option<usize> {
+    let mut depth = 0usize;
+    for (idx, token) in tokens.iter().enumerate() {
+        if is_punct(token, '<') {
+            depth += 1;
+        } else if is_punct(token, '>') && !is_arrow(tokens, idx) {
+            depth -= 1;
+            if depth == 0 {
+                return Some(idx);
+            }
+        }
+    }
+    None
+}
+
+fn parse_generics(tokens: &[TokenTree], item: &mut Struct) {
+    for param in split_top_level(tokens, ',') {
+        if param.is_empty() {
+            continue;
+        }
+        // Defaults are only allowed on the type definition. Equal signs
+        // nested in bounds, as in `Iterator<Item = u8>`, are not defaults.
+        let param = split_top_level(param, '=')[0];
+        item.impl_generics.push(to_string(param));
+
+        match param {
+            [quote, TokenTree::Ident(lifetime), ..] if is_punct(quote, '\'') => {
+                item.type_generics.push(format!("'{}", lifetime));
+            }
+            [keyword, TokenTree::Ident(name), ..] if is_ident(keyword, "const") => {
+                item.type_generics.push(name.to_string());
+            }
+            [TokenTree::Ident(name), ..] => {
+                item.type_generics.push(name.to_string());
+                item.type_params.push(name.to_string());
+            }
+            _ => {}
+        }
+    }
+}
+
+fn parse_fields(stream: TokenStream, named: bool) -> Result<Vec<Field>, String> {
+    let tokens: Vec<TokenTree> = stream.into_iter().collect();
+    let mut fields = Vec::new();
+    for (index, tokens) in split_top_level(&tokens, ',').into_iter().enumerate() {
+        if tokens.is_empty() {
+            continue;
+        }
+        let (attributes, rest) = strip_attributes(tokens);
+        let (member, ty) = if named {
+            match rest {
+                [TokenTree::Ident(name), colon, ty @ ..] if is_punct(colon, ':') => {
+                    (name.to_string(), ty)
+                }
+                _ => return Err(String::from("expected a field name")),
+            }
+        } else {
+            (index.to_string(), rest)
+        };
+
+        let mut field = Field {
+            member,
+            ty: TokenStream::from_iter(ty.iter().cloned()),
+            order: None,
+            desc: false,
+            skip: false,
+        };
+        for attribute in attributes {
+            parse_attribute(attribute, &mut field)?;
+        }
+        fields.push(field);
+    }
+    Ok(fields)
+}
+
+/// Applies a `#[compare(...)]` attribute to `field`. Other attributes are
+/// ignored.
+fn parse_attribute(attribute: &Group, field: &mut Field) -> Result<(), String> {
+    let tokens: Vec<TokenTree> = attribute.stream().into_iter().collect();
+    let args = match tokens.as_slice() {
+        [name, TokenTree::Group(args)] if is_ident(name, "compare") => args.stream(),
+        [name, ..] if is_ident(name, "compare") => {
+            return Err(String::from("expected #[compare(...)]"));
+        }
+        _ => return Ok(()),
+    };
+
+    let args: Vec<TokenTree> = args.into_iter().collect();
+    for arg in split_top_level(&args, ',') {
+        match arg {
+            [] => {}
+            [flag] if is_ident(flag, "skip") => field.skip = true,
+            [flag] if is_ident(flag, "desc") => field.desc = true,
+            [flag] if is_ident(flag, "asc") => field.desc = false,
+            [key, eq, value @ ..] if is_ident(key, "order") && is_punct(eq, '=') => {
+                field.order = Some(parse_order(value)?);
+            }
+            _ => {
+                return Err(format!(
+                    "unknown compare option `{}`, expected `order = n`, `asc`, `desc` or `skip`",
+                    to_string(arg)
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parses the value of `order = n`. The minus sign of a negative order is a
+/// token of its own.
+fn parse_order(tokens: &[TokenTree]) -> Result<i64, String> {
+    let value = match tokens {
+        [minus, literal] if is_punct(minus, '-') => format!("-{}", literal),
+        [literal] => literal.to_string(),
+        _ => to_string(tokens),
+    };
+    value
+        .parse()
+        .map_err(|_| format!("invalid order `{}`, expected an integer", value))
+}
+
+//////////////////////////////////////////////////////////////////////////////
+// Code generation
+
+/// Returns whether `ident` appears anywhere in `tokens`.
+fn mentions(tokens: &TokenStream, ident: &str) -> bool {
+    tokens.clone().into_iter().any(|token| match &token {
+        TokenTree::Group(group) => mentions(&group.stream(), ident),
+        token => is_ident(token, ident),
+    })
+}
+
+/// Returns the header `impl<...> Trait for Name<...> where ...` implementing
+/// `trait_path`, with the type parameters mentioned by the types of `fields`
+/// bound by it.
+fn impl_header(item: &Struct, trait_path: &str, fields: &[&Field]) -> String {
+    let mut predicates = item.predicates.clone();
+    predicates.extend(
+        item.type_params
+            .iter()
+            .filter(|param| fields.iter().any(|field| mentions(&field.ty, param)))
+            .map(|param| format!("{}: {}", param, trait_path)),
+    );
+    format!(
+        "impl<{}> {} for {}<{}> where {}",
+        item.impl_generics.join(", "),
+        trait_path,
+        item.name,
+        item.type_generics.join(", "),
+        predicates.join(", "),
+    )
+}
+
+fn comparator_impl(item: &Struct) -> Result<String, String> {
+    let mut fields: Vec<&Field> = item.fields.list().iter().filter(|f| !f.skip).collect();
+    // Stable sort: fields without an order keep their declaration order, after
+    // the ordered ones.
+    fields.sort_by_key(|field| (field.order.is_none(), field.order));
+
+    let mut body = String::from("::core::cmp::Ordering::Equal");
+    for field in fields.iter().rev() {
+        let (first, second) = if field.desc {
+            ("other", "self")
+        } else {
+            ("self", "other")
+        };
+        body = format!(
+            "match ::quicksort_gen::Comparator::compare(&{}.{member}, &{}.{member}) {{ \
+                ::core::cmp::Ordering::Equal => {}, ordering => ordering }}",
+            first,
+            second,
+            body,
+            member = field.member,
+        );
+    }
+
+    Ok(format!(
+        "{} {{ fn compare(&self, other: &Self) -> ::core::cmp::Ordering {{ {} }} }}",
+        impl_header(item, "::quicksort_gen::Comparator", &fields),
+        body,
+    ))
+}
+
+fn copier_impl(item: &Struct) -> Result<String, String> {
+    let copies: Vec<String> = item
+        .fields
+        .list()
+        .iter()
+        .map(|field| {
+            format!(
+                "{member}: ::quicksort_gen::Copier::copy(&self.{member})",
+                member = field.member
+            )
+        })
+        .collect();
+    let body = match item.fields {
+        Fields::Unit => String::from("Self"),
+        _ => format!("Self {{ {} }}", copies.join(", ")),
+    };
+
+    Ok(format!(
+        "{} {{ fn copy(&self) -> Self {{ {} }} }}",
+        impl_header(
+            item,
+            "::quicksort_gen::Copier",
+            &item.fields.list().iter().collect::<Vec<_>>(),
+        ),
+        body,
+    ))
+}
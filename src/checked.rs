@@ -0,0 +1,251 @@
+//! Sorting which checks that the comparator is a consistent order.
+
+use crate::{permute, Comparator, Sorter};
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+
+/// A violation of the ordering laws by a comparator, found while sorting.
+/// Indices are positions of the elements in the slice before sorting.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ComparatorError {
+    /// Comparing `first` with `second` gave `forward` but comparing them the
+    /// other way round gave `backward`, which is not its reverse.
+    NotAntisymmetric {
+        first: usize,
+        second: usize,
+        forward: Ordering,
+        backward: Ordering,
+    },
+    /// `first` is not greater than `second`, nor `second` than `third`, yet
+    /// `first` is greater than `third`.
+    NotTransitive {
+        first: usize,
+        second: usize,
+        third: usize,
+    },
+    /// Sorting placed `first` right before `second` although `first` is
+    /// greater. A consistent comparator cannot cause this, so the order is
+    /// not transitive over some longer chain of elements.
+    Unordered { first: usize, second: usize },
+}
+
+impl fmt::Display for ComparatorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ComparatorError::NotAntisymmetric {
+                first,
+                second,
+                forward,
+                backward,
+            } => write!(
+                f,
+                "comparator is not antisymmetric: element {} compares {:?} to element {} \
+                 but element {} compares {:?} to element {}",
+                first, forward, second, second, backward, first
+            ),
+            ComparatorError::NotTransitive {
+                first,
+                second,
+                third,
+            } => write!(
+                f,
+                "comparator is not transitive: element {} is not greater than element {}, \
+                 nor element {} than element {}, yet element {} is greater than element {}",
+                first, second, second, third, first, third
+            ),
+            ComparatorError::Unordered { first, second } => write!(
+                f,
+                "comparator is not transitive: element {} was sorted before element {} \
+                 but is greater",
+                first, second
+            ),
+        }
+    }
+}
+
+impl Error for ComparatorError {}
+
+/// Sorts a slice of generic type like [`sort_gen`](crate::sort_gen), checking
+/// that its comparator is a consistent order.
+///
+/// See [`sort_gen_checked_by`].
+pub fn sort_gen_checked<T: Comparator>(arr: &mut [T]) -> Result<&[T], ComparatorError> {
+    sort_gen_checked_by(arr, T::compare)
+}
+
+/// Sorts a slice of generic type with a comparator closure, checking that the
+/// closure is a consistent order.
+///
+/// Every comparison made while sorting is also made the other way round to
+/// check antisymmetry, and the sorted order is then checked against its first
+/// element to catch intransitive comparators, which costs about twice the
+/// comparisons of an unchecked sort. The first violation found is returned and
+/// the slice is then left untouched. Not every violation can be found without
+/// comparing all pairs of elements, so `Ok` only means that none of the
+/// comparisons made contradicted each other.
+///
+/// ```
+/// use quicksort_gen::{sort_gen_checked_by, ComparatorError};
+/// use std::cmp::Ordering;
+///
+/// let mut numbers = vec![3, 1, 3];
+/// // Never returns `Equal`, so the two 3s are each less than the other.
+/// let result = sort_gen_checked_by(&mut numbers, |a: &i32, b: &i32| {
+///     if a > b { Ordering::Greater } else { Ordering::Less }
+/// });
+/// assert!(matches!(
+///     result,
+///     Err(ComparatorError::NotAntisymmetric { first: 0, second: 2, .. })
+///         | Err(ComparatorError::NotAntisymmetric { first: 2, second: 0, .. })
+/// ));
+/// assert_eq!(numbers, vec![3, 1, 3]);
+/// ```
+pub fn sort_gen_checked_by<T, F>(arr: &mut [T], mut compare: F) -> Result<&[T], ComparatorError>
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    // Sorting indices keeps the original positions at hand for errors, and
+    // the slice untouched until the order is known to be consistent.
+    let mut indices: Vec<usize> = (0..arr.len()).collect();
+    let mut error = None;
+    Sorter::new().sort_gen_by(&mut indices, |&a, &b| {
+        if error.is_some() {
+            // The order is known to be broken, finish as fast as possible.
+            return Ordering::Equal;
+        }
+        let forward = compare(&arr[a], &arr[b]);
+        let backward = compare(&arr[b], &arr[a]);
+        if backward != forward.reverse() {
+            error = Some(ComparatorError::NotAntisymmetric {
+                first: a,
+                second: b,
+                forward,
+                backward,
+            });
+        }
+        forward
+    });
+    if let Some(error) = error {
+        return Err(error);
+    }
+    check_transitive(arr, &indices, &mut compare)?;
+
+    permute(arr, &mut indices);
+    Ok(arr)
+}
+
+/// Checks that `arr` ordered by `indices` is sorted, and that the first
+/// element is not greater than any other.
+fn check_transitive<T, F>(
+    arr: &[T],
+    indices: &[usize],
+    compare: &mut F,
+) -> Result<(), ComparatorError>
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let Some(&head) = indices.first() else {
+        return Ok(());
+    };
+    for pair in indices.windows(2) {
+        let (prev, next) = (pair[0], pair[1]);
+        if compare(&arr[prev], &arr[next]) == Ordering::Greater {
+            return Err(ComparatorError::Unordered {
+                first: prev,
+                second: next,
+            });
+        }
+        // The head was checked against `prev` already, unless it is `prev`.
+        if head != prev && compare(&arr[head], &arr[next]) == Ordering::Greater {
+            return Err(ComparatorError::NotTransitive {
+                first: head,
+                second: prev,
+                third: next,
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn test_consistent_comparator() {
+        let mut rng = rand::thread_rng();
+        let mut numbers: Vec<i64> = (0..1_000).map(|_| rng.gen_range(-50..50)).collect();
+        let mut expected = numbers.clone();
+        expected.sort_unstable();
+        assert_eq!(sort_gen_checked(&mut numbers), Ok(&expected[..]));
+
+        let mut empty: Vec<i64> = Vec::new();
+        assert_eq!(sort_gen_checked(&mut empty), Ok(&[][..]));
+    }
+
+    #[test]
+    fn test_not_antisymmetric() {
+        let mut numbers = vec![5, 2, 7, 2];
+        let result = sort_gen_checked_by(&mut numbers, |a: &i32, b: &i32| {
+            if a < b {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            }
+        });
+        match result {
+            Err(ComparatorError::NotAntisymmetric {
+                first,
+                second,
+                forward,
+                backward,
+            }) => {
+                let mut pair = [first, second];
+                pair.sort_unstable();
+                assert_eq!(pair, [1, 3]);
+                assert_eq!((forward, backward), (Ordering::Greater, Ordering::Greater));
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert_eq!(numbers, vec![5, 2, 7, 2]);
+    }
+
+    #[test]
+    fn test_not_transitive() {
+        // Rock, paper, scissors: each beats the next one round the circle.
+        let beats = |a: &u8, b: &u8| match (b + 3 - a) % 3 {
+            0 => Ordering::Equal,
+            1 => Ordering::Less,
+            _ => Ordering::Greater,
+        };
+        let mut hands = vec![0u8, 1, 2];
+        assert_eq!(
+            sort_gen_checked_by(&mut hands, beats),
+            Err(ComparatorError::NotTransitive {
+                first: 0,
+                second: 1,
+                third: 2
+            })
+        );
+        assert_eq!(hands, vec![0, 1, 2]);
+
+        let mut hands: Vec<u8> = (0..300).map(|n| (n % 3) as u8).collect();
+        assert!(sort_gen_checked_by(&mut hands, beats).is_err());
+    }
+
+    #[test]
+    fn test_error_message() {
+        let error = ComparatorError::NotTransitive {
+            first: 4,
+            second: 0,
+            third: 2,
+        };
+        assert_eq!(
+            error.to_string(),
+            "comparator is not transitive: element 4 is not greater than element 0, \
+             nor element 0 than element 2, yet element 4 is greater than element 2"
+        );
+    }
+}
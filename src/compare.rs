@@ -0,0 +1,188 @@
+//! Combinators building orderings out of smaller ones.
+//!
+//! Two kinds of adaptors are provided. Wrapper types such as [`Reverse`],
+//! [`NullsFirst`] and [`NullsLast`] implement [`Comparator`] on top of the
+//! comparator of the wrapped value, and are meant to be returned from key
+//! functions. Comparator closures, as taken by `sort_gen_by`, are built with
+//! [`by_key`], [`nulls_first`] and [`nulls_last`], and chained with the
+//! methods of [`CompareExt`].
+//!
+//! ```
+//! use quicksort_gen::compare::{by_key, CompareExt};
+//! use quicksort_gen::sort_gen_by;
+//!
+//! struct Node {
+//!     pid: u64,
+//!     name: String,
+//! }
+//!
+//! let mut nodes = vec![
+//!     Node { pid: 2, name: String::from("kobj") },
+//!     Node { pid: 1, name: String::from("init") },
+//!     Node { pid: 2, name: String::from("systemd") },
+//! ];
+//! // Pid ascending, then name descending.
+//! sort_gen_by(
+//!     &mut nodes,
+//!     by_key(|n: &Node| n.pid).then_with((|a: &Node, b: &Node| a.name.cmp(&b.name)).reverse()),
+//! );
+//! let names: Vec<&str> = nodes.iter().map(|n| n.name.as_str()).collect();
+//! assert_eq!(names, vec!["init", "systemd", "kobj"]);
+//! ```
+
+use crate::Comparator;
+use std::cmp::Ordering;
+
+/// Orders the wrapped values in reverse.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Reverse<C>(pub C);
+
+impl<C: Comparator> Comparator for Reverse<C> {
+    fn compare(&self, other: &Self) -> Ordering {
+        other.0.compare(&self.0)
+    }
+}
+
+/// Orders optional values with `None` before every `Some`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct NullsFirst<C>(pub Option<C>);
+
+impl<C: Comparator> Comparator for NullsFirst<C> {
+    fn compare(&self, other: &Self) -> Ordering {
+        compare_options(&self.0, &other.0, &mut C::compare, Ordering::Less)
+    }
+}
+
+/// Orders optional values with `None` after every `Some`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct NullsLast<C>(pub Option<C>);
+
+impl<C: Comparator> Comparator for NullsLast<C> {
+    fn compare(&self, other: &Self) -> Ordering {
+        compare_options(&self.0, &other.0, &mut C::compare, Ordering::Greater)
+    }
+}
+
+/// Compares two options with `compare`, where `none` is how `None` compares
+/// to any `Some`.
+fn compare_options<T, F>(a: &Option<T>, b: &Option<T>, compare: &mut F, none: Ordering) -> Ordering
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    match (a, b) {
+        (Some(a), Some(b)) => compare(a, b),
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => none,
+        (Some(_), None) => none.reverse(),
+    }
+}
+
+/// Returns a comparator closure comparing the keys extracted by `key`.
+pub fn by_key<T, K, F>(mut key: F) -> impl FnMut(&T, &T) -> Ordering
+where
+    K: Comparator,
+    F: FnMut(&T) -> K,
+{
+    move |a, b| key(a).compare(&key(b))
+}
+
+/// Returns a comparator closure over options which orders `None` before every
+/// `Some`, and `Some` values with `compare`.
+pub fn nulls_first<T, F>(mut compare: F) -> impl FnMut(&Option<T>, &Option<T>) -> Ordering
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    move |a, b| compare_options(a, b, &mut compare, Ordering::Less)
+}
+
+/// Returns a comparator closure over options which orders `None` after every
+/// `Some`, and `Some` values with `compare`.
+pub fn nulls_last<T, F>(mut compare: F) -> impl FnMut(&Option<T>, &Option<T>) -> Ordering
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    move |a, b| compare_options(a, b, &mut compare, Ordering::Greater)
+}
+
+/// Chaining methods for comparator closures.
+pub trait CompareExt<T>: FnMut(&T, &T) -> Ordering + Sized {
+    /// Returns a comparator which orders by `self`, and elements equal under
+    /// `self` by `next`.
+    fn then_with<F>(mut self, mut next: F) -> impl FnMut(&T, &T) -> Ordering
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        move |a, b| match self(a, b) {
+            Ordering::Equal => next(a, b),
+            ordering => ordering,
+        }
+    }
+
+    /// Returns a comparator which orders in reverse of `self`.
+    fn reverse(mut self) -> impl FnMut(&T, &T) -> Ordering {
+        move |a, b| self(b, a)
+    }
+}
+
+impl<T, F: FnMut(&T, &T) -> Ordering> CompareExt<T> for F {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sort_gen, sort_gen_by};
+
+    #[test]
+    fn test_wrappers() {
+        let mut numbers = vec![Reverse(1), Reverse(3), Reverse(2)];
+        sort_gen(&mut numbers);
+        assert_eq!(numbers, vec![Reverse(3), Reverse(2), Reverse(1)]);
+
+        let mut first = vec![NullsFirst(Some(2)), NullsFirst(None), NullsFirst(Some(1))];
+        sort_gen(&mut first);
+        assert_eq!(
+            first,
+            vec![NullsFirst(None), NullsFirst(Some(1)), NullsFirst(Some(2))]
+        );
+
+        let mut last = vec![NullsLast(Some(2)), NullsLast(None), NullsLast(Some(1))];
+        sort_gen(&mut last);
+        assert_eq!(
+            last,
+            vec![NullsLast(Some(1)), NullsLast(Some(2)), NullsLast(None)]
+        );
+
+        let mut nested = vec![
+            NullsLast(Some(Reverse(1))),
+            NullsLast(None),
+            NullsLast(Some(Reverse(2))),
+        ];
+        sort_gen(&mut nested);
+        assert_eq!(
+            nested,
+            vec![
+                NullsLast(Some(Reverse(2))),
+                NullsLast(Some(Reverse(1))),
+                NullsLast(None)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_closures() {
+        let mut records = vec![(2, "b"), (1, "z"), (2, "a"), (1, "y")];
+        sort_gen_by(
+            &mut records,
+            by_key(|r: &(i32, &str)| r.0).then_with(by_key(|r: &(i32, &str)| Reverse(r.1))),
+        );
+        assert_eq!(records, vec![(1, "z"), (1, "y"), (2, "b"), (2, "a")]);
+
+        sort_gen_by(&mut records, by_key(|r: &(i32, &str)| r.1).reverse());
+        assert_eq!(records, vec![(1, "z"), (1, "y"), (2, "b"), (2, "a")]);
+
+        let mut parents = vec![Some(3), None, Some(1)];
+        sort_gen_by(&mut parents, nulls_last(i32::cmp));
+        assert_eq!(parents, vec![Some(1), Some(3), None]);
+        sort_gen_by(&mut parents, nulls_first(|a: &i32, b: &i32| b.cmp(a)));
+        assert_eq!(parents, vec![None, Some(3), Some(1)]);
+    }
+}
@@ -0,0 +1,166 @@
+//! Yaroslavskiy's dual-pivot quicksort.
+//!
+//! Each step partitions the slice around two pivots `p <= q` into three parts:
+//! elements less than `p`, elements between `p` and `q`, and elements greater
+//! than `q`.
+
+use crate::heapsort::heapsort_gen;
+use crate::stats::Recorder;
+use std::cmp::Ordering;
+
+/// Slices at least this long pick their pivots from five evenly spread samples
+/// instead of the two end elements.
+const SAMPLE_THRESHOLD: usize = 7;
+
+/// Moves the second and fourth of five sorted samples to the ends of `arr`, so
+/// the pivots split the slice in roughly equal thirds even on presorted input.
+fn choose_pivots<T, F, S>(arr: &mut [T], compare: &mut F, stats: &mut S)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    S: Recorder,
+{
+    let len = arr.len();
+    let seventh = len / 7;
+    let mid = len / 2;
+    let samples = [
+        mid - 2 * seventh,
+        mid - seventh,
+        mid,
+        mid + seventh,
+        mid + 2 * seventh,
+    ];
+
+    for i in 1..samples.len() {
+        let mut j = i;
+        while j > 0 && compare(&arr[samples[j]], &arr[samples[j - 1]]) == Ordering::Less {
+            stats.swap(arr, samples[j], samples[j - 1]);
+            j -= 1;
+        }
+    }
+    stats.swap(arr, 0, samples[1]);
+    stats.swap(arr, len - 1, samples[3]);
+}
+
+/// Sorts `arr`, switching to heapsort once the partitioning gets `limit`
+/// levels deep.
+///
+/// The sort does not recurse. After each partition the two larger parts are
+/// pushed on an explicit stack and the smallest part is sorted first, so the
+/// stack holds O(log n) ranges whatever the pivots.
+pub(crate) fn dual_pivot_gen<T, F, S>(arr: &mut [T], compare: &mut F, limit: usize, stats: &mut S)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    S: Recorder,
+{
+    let mut pending = vec![(0, arr.len(), limit, 1)];
+
+    while let Some((mut lo, mut hi, mut limit, mut depth)) = pending.pop() {
+        while hi - lo > 1 {
+            let v = &mut arr[lo..hi];
+            if limit == 0 {
+                stats.fallback();
+                heapsort_gen(v, compare, stats);
+                break;
+            }
+            limit -= 1;
+
+            stats.partition(depth);
+            let (lt, gt, equal_pivots) = partition_dual(v, compare, stats);
+            let mut parts = [(lo, lo + lt), (lo + lt + 1, lo + gt), (lo + gt + 1, hi)];
+            // Equal pivots leave only elements equal to both in the middle.
+            if equal_pivots {
+                parts[1] = (lo, lo);
+            }
+            // Order the parts from largest to smallest. The medium part is
+            // sorted right after the smallest one and is at most half of the
+            // range, which bounds the stack.
+            let size = |(start, end): (usize, usize)| end - start;
+            for &(a, b) in [(0, 1), (1, 2), (0, 1)].iter() {
+                if size(parts[a]) < size(parts[b]) {
+                    parts.swap(a, b);
+                }
+            }
+            depth += 1;
+            pending.push((parts[0].0, parts[0].1, limit, depth));
+            pending.push((parts[1].0, parts[1].1, limit, depth));
+            (lo, hi) = parts[2];
+        }
+    }
+}
+
+/// Partitions `arr` around two pivots `p <= q`. Returns the final positions of
+/// `p` and `q`, and whether they compare equal.
+fn partition_dual<T, F, S>(arr: &mut [T], compare: &mut F, stats: &mut S) -> (usize, usize, bool)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    S: Recorder,
+{
+    let len = arr.len();
+    if len >= SAMPLE_THRESHOLD {
+        choose_pivots(arr, compare, stats);
+    }
+
+    let last = len - 1;
+    if compare(&arr[last], &arr[0]) == Ordering::Less {
+        stats.swap(arr, 0, last);
+    }
+
+    // arr[1..lt] < p, arr[lt..idx] in [p, q], arr[gt + 1..last] > q.
+    let (mut lt, mut idx, mut gt) = (1, 1, last - 1);
+    while idx <= gt {
+        if compare(&arr[idx], &arr[0]) == Ordering::Less {
+            stats.swap(arr, idx, lt);
+            lt += 1;
+        } else if compare(&arr[idx], &arr[last]) == Ordering::Greater {
+            while idx < gt && compare(&arr[gt], &arr[last]) == Ordering::Greater {
+                gt -= 1;
+            }
+            stats.swap(arr, idx, gt);
+            gt -= 1;
+            if compare(&arr[idx], &arr[0]) == Ordering::Less {
+                stats.swap(arr, idx, lt);
+                lt += 1;
+            }
+        }
+        idx += 1;
+    }
+    lt -= 1;
+    gt += 1;
+    stats.swap(arr, 0, lt);
+    stats.swap(arr, last, gt);
+
+    let equal_pivots = compare(&arr[lt], &arr[gt]) == Ordering::Equal;
+    (lt, gt, equal_pivots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn test_dual_pivot() {
+        let mut rng = rand::thread_rng();
+        for len in 0..100 {
+            let mut numbers: Vec<i64> = (0..len).map(|_| rng.gen_range(-10..10)).collect();
+            let mut expected = numbers.clone();
+            expected.sort_unstable();
+            dual_pivot_gen(&mut numbers, &mut i64::cmp, usize::MAX, &mut ());
+            assert_eq!(numbers, expected);
+        }
+    }
+
+    #[test]
+    fn test_dual_pivot_presorted() {
+        let mut numbers: Vec<i64> = (0..100_000).collect();
+        dual_pivot_gen(&mut numbers, &mut i64::cmp, usize::MAX, &mut ());
+        assert!(numbers.windows(2).all(|w| w[0] <= w[1]));
+
+        numbers.reverse();
+        dual_pivot_gen(&mut numbers, &mut i64::cmp, usize::MAX, &mut ());
+        assert!(numbers.windows(2).all(|w| w[0] <= w[1]));
+
+        let mut equal = vec![1i64; 100_000];
+        dual_pivot_gen(&mut equal, &mut i64::cmp, usize::MAX, &mut ());
+    }
+}
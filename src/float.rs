@@ -0,0 +1,181 @@
+//! Sorting floating point numbers.
+//!
+//! Floats are only partially ordered: NaN compares to nothing, not even
+//! itself, so they cannot implement [`Comparator`] directly. A [`NanPolicy`]
+//! turns the partial order into a total one, either by moving NaNs to one end
+//! ([`NanFirst`], [`NanLast`]) or by following the IEEE 754 totalOrder
+//! predicate ([`TotalOrder`]). The policy is used by [`sort_f64`] and
+//! [`sort_f32`], and by the [`FloatOrder`] wrapper for floats nested in other
+//! types.
+//!
+//! ```
+//! use quicksort_gen::float::{FloatOrder, NanFirst, NanLast};
+//! use quicksort_gen::{sort_f64, sort_gen_by_key};
+//!
+//! let mut readings = vec![2.5, f64::NAN, -1.0, 0.0];
+//! sort_f64(&mut readings, NanLast);
+//! assert_eq!(&readings[..3], &[-1.0, 0.0, 2.5]);
+//! assert!(readings[3].is_nan());
+//!
+//! let mut sensors = vec![("b", 1.5), ("a", f64::NAN)];
+//! sort_gen_by_key(&mut sensors, |s| FloatOrder(s.1, NanFirst));
+//! assert_eq!(sensors[0].0, "a");
+//! ```
+
+use crate::{Comparator, Sorter};
+use std::cmp::Ordering;
+
+/// A floating point type, `f32` or `f64`.
+pub trait Float: Copy + PartialOrd {
+    fn is_nan(self) -> bool;
+
+    /// Compares with the IEEE 754 totalOrder predicate.
+    fn total_cmp(&self, other: &Self) -> Ordering;
+}
+
+impl Float for f32 {
+    fn is_nan(self) -> bool {
+        f32::is_nan(self)
+    }
+
+    fn total_cmp(&self, other: &Self) -> Ordering {
+        f32::total_cmp(self, other)
+    }
+}
+
+impl Float for f64 {
+    fn is_nan(self) -> bool {
+        f64::is_nan(self)
+    }
+
+    fn total_cmp(&self, other: &Self) -> Ordering {
+        f64::total_cmp(self, other)
+    }
+}
+
+/// A total order over floats.
+pub trait NanPolicy {
+    fn compare<F: Float>(a: &F, b: &F) -> Ordering;
+}
+
+/// Orders NaNs before every number. Numbers are ordered by value, so `-0.0`
+/// and `0.0` are equal, and NaNs are all equal to each other.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct NanFirst;
+
+impl NanPolicy for NanFirst {
+    fn compare<F: Float>(a: &F, b: &F) -> Ordering {
+        compare_nan(a, b, Ordering::Less)
+    }
+}
+
+/// Orders NaNs after every number. Numbers are ordered by value, so `-0.0`
+/// and `0.0` are equal, and NaNs are all equal to each other.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct NanLast;
+
+impl NanPolicy for NanLast {
+    fn compare<F: Float>(a: &F, b: &F) -> Ordering {
+        compare_nan(a, b, Ordering::Greater)
+    }
+}
+
+/// Orders floats by the IEEE 754 totalOrder predicate: negative NaNs, then
+/// numbers from `-inf` to `inf` with `-0.0` before `0.0`, then positive NaNs.
+/// Only bitwise identical floats are equal.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct TotalOrder;
+
+impl NanPolicy for TotalOrder {
+    fn compare<F: Float>(a: &F, b: &F) -> Ordering {
+        a.total_cmp(b)
+    }
+}
+
+/// Compares two floats by value, where `nan` is how NaN compares to any
+/// number.
+fn compare_nan<F: Float>(a: &F, b: &F, nan: Ordering) -> Ordering {
+    match (a.is_nan(), b.is_nan()) {
+        (false, false) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+        (true, true) => Ordering::Equal,
+        (true, false) => nan,
+        (false, true) => nan.reverse(),
+    }
+}
+
+/// Orders the wrapped float with the NaN policy `P`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FloatOrder<F, P = NanLast>(pub F, pub P);
+
+impl<F: Float, P: NanPolicy> Comparator for FloatOrder<F, P> {
+    fn compare(&self, other: &Self) -> Ordering {
+        P::compare(&self.0, &other.0)
+    }
+}
+
+/// Sorts f64 elements in a slice, placing NaNs as told by `policy`.
+pub fn sort_f64<P: NanPolicy>(arr: &mut [f64], policy: P) -> &[f64] {
+    sort_float(arr, policy)
+}
+
+/// Sorts f32 elements in a slice, placing NaNs as told by `policy`.
+pub fn sort_f32<P: NanPolicy>(arr: &mut [f32], policy: P) -> &[f32] {
+    sort_float(arr, policy)
+}
+
+fn sort_float<F: Float, P: NanPolicy>(arr: &mut [F], _policy: P) -> &[F] {
+    Sorter::new().sort_gen_by(arr, P::compare)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sort_gen, sort_gen_by_key};
+
+    fn bits(numbers: &[f64]) -> Vec<u64> {
+        numbers.iter().map(|n| n.to_bits()).collect()
+    }
+
+    #[test]
+    fn test_nan_first_and_last() {
+        let mut numbers = vec![3.0, f64::NAN, -0.0, f64::INFINITY, -2.0, -f64::NAN, 0.0];
+        sort_f64(&mut numbers, NanFirst);
+        assert!(numbers[..2].iter().all(|n| n.is_nan()));
+        assert_eq!(&numbers[2..3], &[-2.0]);
+        assert!(numbers[3..5].iter().all(|&n| n == 0.0));
+        assert_eq!(&numbers[5..], &[3.0, f64::INFINITY]);
+
+        sort_f64(&mut numbers, NanLast);
+        assert_eq!(&numbers[..1], &[-2.0]);
+        assert_eq!(&numbers[3..5], &[3.0, f64::INFINITY]);
+        assert!(numbers[5..].iter().all(|n| n.is_nan()));
+
+        let mut numbers = vec![1.5f32, f32::NAN, f32::NEG_INFINITY];
+        sort_f32(&mut numbers, NanLast);
+        assert_eq!(&numbers[..2], &[f32::NEG_INFINITY, 1.5]);
+        assert!(numbers[2].is_nan());
+    }
+
+    #[test]
+    fn test_total_order() {
+        let mut numbers = vec![f64::NAN, 1.0, 0.0, -f64::NAN, -0.0, f64::NEG_INFINITY];
+        sort_f64(&mut numbers, TotalOrder);
+        assert_eq!(
+            bits(&numbers),
+            bits(&[-f64::NAN, f64::NEG_INFINITY, -0.0, 0.0, 1.0, f64::NAN])
+        );
+    }
+
+    #[test]
+    fn test_float_order() {
+        let mut readings = vec![(f32::NAN, 1), (0.5, 2), (-0.5, 3)];
+        sort_gen_by_key(&mut readings, |r| FloatOrder(r.0, NanFirst));
+        let ids: Vec<i32> = readings.iter().map(|r| r.1).collect();
+        assert_eq!(ids, vec![1, 3, 2]);
+
+        let mut readings = vec![FloatOrder(f64::NAN, NanLast), FloatOrder(-1.0, NanLast)];
+        sort_gen(&mut readings);
+        assert_eq!(readings[0].0, -1.0);
+        assert!(readings[1].0.is_nan());
+    }
+}
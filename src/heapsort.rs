@@ -0,0 +1,59 @@
+//! Heapsort, used as the worst-case fallback of the quicksort engines.
+
+use crate::stats::Recorder;
+use std::cmp::Ordering;
+
+/// Restores the max-heap property of `heap` below `node`.
+fn sift_down<T, F, S>(heap: &mut [T], mut node: usize, compare: &mut F, stats: &mut S)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    S: Recorder,
+{
+    loop {
+        let mut child = 2 * node + 1;
+        if child >= heap.len() {
+            break;
+        }
+        if child + 1 < heap.len() && compare(&heap[child], &heap[child + 1]) == Ordering::Less {
+            child += 1;
+        }
+        if compare(&heap[node], &heap[child]) != Ordering::Less {
+            break;
+        }
+        stats.swap(heap, node, child);
+        node = child;
+    }
+}
+
+/// Sorts `arr` in O(n log n) time and constant space.
+pub(crate) fn heapsort_gen<T, F, S>(arr: &mut [T], compare: &mut F, stats: &mut S)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    S: Recorder,
+{
+    for node in (0..arr.len() / 2).rev() {
+        sift_down(arr, node, compare, stats);
+    }
+    for end in (1..arr.len()).rev() {
+        stats.swap(arr, 0, end);
+        sift_down(&mut arr[..end], 0, compare, stats);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn test_heapsort() {
+        let mut rng = rand::thread_rng();
+        for len in 0..64 {
+            let mut numbers: Vec<i64> = (0..len).map(|_| rng.gen_range(-20..20)).collect();
+            let mut expected = numbers.clone();
+            expected.sort_unstable();
+            heapsort_gen(&mut numbers, &mut i64::cmp, &mut ());
+            assert_eq!(numbers, expected);
+        }
+    }
+}
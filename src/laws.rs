@@ -0,0 +1,316 @@
+//! Checking that `Comparator` and `Copier` implementations obey the laws
+//! sorting relies on.
+
+use crate::{Comparator, Copier};
+use rand::RngCore;
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+
+/// Number of values generated per round of [`check_comparator_laws_with`].
+const POOL_SIZE: usize = 8;
+
+/// A law which a comparator must obey for sorting to work.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Law {
+    /// Every value is equal to itself.
+    Reflexivity,
+    /// Comparing `b` with `a` gives the reverse of comparing `a` with `b`.
+    Antisymmetry,
+    /// If `a` is not greater than `b`, nor `b` than `c`, then `a` is not
+    /// greater than `c`.
+    Transitivity,
+    /// A copy of a value is equal to it and compares to every other value
+    /// like the value itself.
+    CopyConsistency,
+}
+
+impl fmt::Display for Law {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Law::Reflexivity => "reflexivity",
+            Law::Antisymmetry => "antisymmetry",
+            Law::Transitivity => "transitivity",
+            Law::CopyConsistency => "consistency with copy",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A law broken by a comparator, along with the values breaking it, in the
+/// order the law names them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LawViolation<T> {
+    pub law: Law,
+    pub counterexample: Vec<T>,
+}
+
+impl<T: fmt::Debug> fmt::Display for LawViolation<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "comparator violates {} for {:?}",
+            self.law, self.counterexample
+        )
+    }
+}
+
+impl<T: fmt::Debug> Error for LawViolation<T> {}
+
+/// Checks the comparator and copier of `T` against every value, pair and
+/// triple of `samples`.
+///
+/// Laws over fewer values are checked first, so the counterexample returned
+/// is as small as the samples allow: a single value breaking reflexivity or
+/// consistency with its copy, then a pair breaking antisymmetry or
+/// consistency with a copy, and only then a triple breaking transitivity.
+/// The counterexample is moved out of `samples` rather than copied, so it
+/// shows the values as they are even when the copier is the broken part.
+/// Takes O(n^3) comparisons, so keep the samples to a few hundred values.
+///
+/// ```
+/// use quicksort_gen::{check_comparator_laws, Law};
+///
+/// assert!(check_comparator_laws([3, 1, 2, 2]).is_ok());
+///
+/// #[derive(Clone, Debug, PartialEq)]
+/// struct Reading(f64);
+///
+/// impl quicksort_gen::Comparator for Reading {
+///     fn compare(&self, other: &Self) -> std::cmp::Ordering {
+///         self.0.partial_cmp(&other.0).unwrap_or(std::cmp::Ordering::Less)
+///     }
+/// }
+///
+/// let violation = check_comparator_laws([Reading(1.0), Reading(f64::NAN)]).unwrap_err();
+/// assert_eq!(violation.law, Law::Reflexivity);
+/// assert!(violation.counterexample[0].0.is_nan());
+/// ```
+pub fn check_comparator_laws<T, S>(samples: S) -> Result<(), LawViolation<T>>
+where
+    T: Comparator + Copier,
+    S: Into<Vec<T>>,
+{
+    let samples = samples.into();
+    match find_violation(&samples) {
+        Some((law, indices)) => {
+            // The indices are distinct, so each sample is moved out at most
+            // once.
+            let mut samples: Vec<Option<T>> = samples.into_iter().map(Some).collect();
+            Err(LawViolation {
+                law,
+                counterexample: indices
+                    .into_iter()
+                    .filter_map(|i| samples[i].take())
+                    .collect(),
+            })
+        }
+        None => Ok(()),
+    }
+}
+
+/// Checks the comparator and copier of `T` like [`check_comparator_laws`],
+/// on `rounds` sets of values drawn by `generate`.
+///
+/// Every round draws a handful of values and checks all their values, pairs
+/// and triples, so generators should draw from small domains where equal and
+/// nearly equal values are likely.
+///
+/// ```
+/// use quicksort_gen::check_comparator_laws_with;
+/// use rand::Rng;
+///
+/// let result = check_comparator_laws_with(|rng| (rng.gen_range(0..3), rng.gen_range(0..3)), 100);
+/// assert!(result.is_ok());
+/// ```
+pub fn check_comparator_laws_with<T, G>(
+    mut generate: G,
+    rounds: usize,
+) -> Result<(), LawViolation<T>>
+where
+    T: Comparator + Copier,
+    G: FnMut(&mut dyn RngCore) -> T,
+{
+    let mut rng = rand::thread_rng();
+    for _ in 0..rounds {
+        let samples: Vec<T> = (0..POOL_SIZE).map(|_| generate(&mut rng)).collect();
+        check_comparator_laws(samples)?;
+    }
+    Ok(())
+}
+
+/// Finds the first law broken by `samples`, along with the distinct indices
+/// of the samples breaking it.
+fn find_violation<T>(samples: &[T]) -> Option<(Law, Vec<usize>)>
+where
+    T: Comparator + Copier,
+{
+    for (i, a) in samples.iter().enumerate() {
+        if a.compare(a) != Ordering::Equal {
+            return Some((Law::Reflexivity, vec![i]));
+        }
+        let copy = a.copy();
+        if a.compare(&copy) != Ordering::Equal || copy.compare(a) != Ordering::Equal {
+            return Some((Law::CopyConsistency, vec![i]));
+        }
+    }
+
+    // Pairs and triples repeating a value cannot break a law which every
+    // single value obeys, so they are skipped.
+    for (i, a) in samples.iter().enumerate() {
+        let copy = a.copy();
+        for (j, b) in samples.iter().enumerate() {
+            if i == j {
+                continue;
+            }
+            if b.compare(a) != a.compare(b).reverse() {
+                return Some((Law::Antisymmetry, vec![i, j]));
+            }
+            if copy.compare(b) != a.compare(b) {
+                return Some((Law::CopyConsistency, vec![i, j]));
+            }
+        }
+    }
+
+    for (i, a) in samples.iter().enumerate() {
+        for (j, b) in samples.iter().enumerate() {
+            if i == j || a.compare(b) == Ordering::Greater {
+                continue;
+            }
+            for (k, c) in samples.iter().enumerate() {
+                if k == i || k == j {
+                    continue;
+                }
+                if b.compare(c) != Ordering::Greater && a.compare(c) == Ordering::Greater {
+                    return Some((Law::Transitivity, vec![i, j, k]));
+                }
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    /// A value with a comparator and copier broken in configurable ways.
+    #[derive(Debug, PartialEq)]
+    struct Faulty {
+        value: u8,
+        fault: Option<Law>,
+    }
+
+    impl Comparator for Faulty {
+        fn compare(&self, other: &Self) -> Ordering {
+            let ordering = self.value.cmp(&other.value);
+            match self.fault {
+                Some(Law::Reflexivity) if ordering == Ordering::Equal => Ordering::Less,
+                Some(Law::Antisymmetry) if ordering == Ordering::Less => Ordering::Equal,
+                // Each value is greater than the next one round a circle.
+                Some(Law::Transitivity) => match (other.value + 3 - self.value) % 3 {
+                    0 => Ordering::Equal,
+                    1 => Ordering::Greater,
+                    _ => Ordering::Less,
+                },
+                _ => ordering,
+            }
+        }
+    }
+
+    impl Copier for Faulty {
+        fn copy(&self) -> Self {
+            let value = match self.fault {
+                Some(Law::CopyConsistency) => self.value.wrapping_add(1),
+                _ => self.value,
+            };
+            Faulty {
+                value,
+                fault: self.fault,
+            }
+        }
+    }
+
+    fn faulty(values: &[u8], fault: Law) -> Vec<Faulty> {
+        values
+            .iter()
+            .map(|&value| Faulty {
+                value,
+                fault: Some(fault),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_lawful_types() {
+        assert_eq!(check_comparator_laws(Vec::<i64>::new()), Ok(()));
+        assert_eq!(check_comparator_laws([5, -1, 5, 0]), Ok(()));
+        assert_eq!(
+            check_comparator_laws_with(|rng| Some(rng.gen_range(0..4)), 50),
+            Ok(())
+        );
+        assert_eq!(
+            check_comparator_laws_with(|rng| vec![rng.gen_range(0..2); rng.gen_range(0..3)], 50),
+            Ok(())
+        );
+    }
+
+    fn values(violation: &LawViolation<Faulty>) -> Vec<u8> {
+        violation.counterexample.iter().map(|f| f.value).collect()
+    }
+
+    #[test]
+    fn test_minimal_counterexamples() {
+        let samples = faulty(&[2, 1, 1], Law::Reflexivity);
+        let violation = check_comparator_laws(samples).unwrap_err();
+        assert_eq!(violation.law, Law::Reflexivity);
+        assert_eq!(values(&violation), vec![2]);
+
+        let samples = faulty(&[4, 4, 9], Law::Antisymmetry);
+        let violation = check_comparator_laws(samples).unwrap_err();
+        assert_eq!(violation.law, Law::Antisymmetry);
+        assert_eq!(values(&violation), vec![4, 9]);
+
+        let samples = faulty(&[0, 1, 2], Law::Transitivity);
+        let violation = check_comparator_laws(samples).unwrap_err();
+        assert_eq!(violation.law, Law::Transitivity);
+        assert_eq!(values(&violation), vec![0, 2, 1]);
+
+        // The copier adds one, which must not leak into the counterexample.
+        let samples = faulty(&[7], Law::CopyConsistency);
+        let violation = check_comparator_laws(samples).unwrap_err();
+        assert_eq!(violation.law, Law::CopyConsistency);
+        assert_eq!(values(&violation), vec![7]);
+    }
+
+    #[test]
+    fn test_generated_samples() {
+        let violation = check_comparator_laws_with(
+            |rng| Faulty {
+                value: rng.gen_range(0..3),
+                fault: Some(Law::Transitivity),
+            },
+            100,
+        )
+        .unwrap_err();
+        assert_eq!(violation.law, Law::Transitivity);
+        assert_eq!(
+            violation.to_string(),
+            format!(
+                "comparator violates transitivity for {:?}",
+                violation.counterexample
+            )
+        );
+
+        let violation = check_comparator_laws_with(
+            |_| Faulty {
+                value: 7,
+                fault: Some(Law::CopyConsistency),
+            },
+            1,
+        )
+        .unwrap_err();
+        assert_eq!(violation.counterexample, faulty(&[7], Law::CopyConsistency));
+    }
+}
@@ -2,34 +2,69 @@
 /// Here term 'generic' refers to any type of objects where a partial order can
 /// be established will be sorted.
 use std::cmp::Ordering;
+use std::ops::{Bound, RangeBounds};
 
-fn partition(arr: &mut Vec<i64>, low: usize, high: usize) -> usize {
-    let pivot = arr[high - 1];
-    let mut idx = low;
+// Lets code generated by the derive macros name this crate from within it.
+extern crate self as quicksort_gen;
 
-    for j in low..high {
-        if arr[j] <= pivot {
-            arr.swap(idx, j);
-            idx = idx + 1;
-        }
-    }
-    idx
+mod checked;
+pub mod compare;
+mod dual_pivot;
+pub mod float;
+mod heapsort;
+mod laws;
+mod parallel;
+pub mod partition;
+mod pdq;
+pub mod pivot;
+mod quicksort;
+mod select;
+mod sorter;
+pub mod statistics;
+mod stats;
+mod topk;
+mod verify;
+
+pub use checked::{sort_gen_checked, sort_gen_checked_by, ComparatorError};
+pub use float::{sort_f32, sort_f64};
+pub use laws::{check_comparator_laws, check_comparator_laws_with, Law, LawViolation};
+#[cfg(feature = "derive")]
+pub use quicksort_gen_derive::{Comparator, Copier};
+pub use select::{
+    partial_sort_gen, partial_sort_gen_by, select_nth, select_nth_gen, select_nth_gen_by,
+    sort_range_of_ranks, sort_range_of_ranks_by,
+};
+pub use sorter::{Algorithm, Sorter};
+pub use stats::SortStats;
+pub use topk::TopK;
+pub use verify::{
+    is_sorted_by, is_sorted_gen, verify_sorted_permutation, verify_sorted_permutation_by,
+    VerifyError,
+};
+
+/// Converts any range over indices into a pair of bounds which can be used to
+/// index a slice.
+fn bounds<R: RangeBounds<usize>>(range: R) -> (Bound<usize>, Bound<usize>) {
+    (range.start_bound().cloned(), range.end_bound().cloned())
 }
 
-fn quicksort(arr: &mut Vec<i64>, low: usize, high: usize) -> &Vec<i64> {
-    if low >= usize::MIN && high >= usize::MIN {
-        if low < high {
-            let mid = partition(arr, low, high);
-            quicksort(arr, low, mid - 1);
-            quicksort(arr, mid, high);
-        }
-    }
-    arr
+/// Sorts i64 elements in a slice.
+pub fn sort(arr: &mut [i64]) -> &[i64] {
+    Sorter::new().sort(arr)
 }
 
-/// Sorts i64 elements in a vector.
-pub fn sort(arr: &mut Vec<i64>) -> &Vec<i64> {
-    quicksort(arr, usize::MIN, arr.len())
+/// Sorts i64 elements in a slice and returns the work done, see
+/// [`SortStats`].
+pub fn sort_stats(arr: &mut [i64]) -> SortStats {
+    Sorter::new().sort_stats(arr)
+}
+
+/// Sorts i64 elements lying in `range` of a slice, leaving the rest of the
+/// slice untouched. Returns the sorted sub-slice.
+///
+/// Panics if the range is out of bounds of the slice.
+pub fn sort_range<R: RangeBounds<usize>>(arr: &mut [i64], range: R) -> &[i64] {
+    sort(&mut arr[bounds(range)])
 }
 
 //////////////////////////////////////////////////////////////////////////////
@@ -37,50 +72,141 @@ pub fn sort(arr: &mut Vec<i64>) -> &Vec<i64> {
 
 /// Defined a partially-ordered comparator to be used to compare objects while
 /// sorting.
+///
+/// Sorting is panic safe. If `compare`, a comparator closure or a key function
+/// panics, the panic reaches the caller and the slice is left in an
+/// unspecified order, but it still holds every one of its elements exactly
+/// once: elements are only ever swapped, so none is lost, duplicated or
+/// dropped twice. This holds for every sorting, selection and parallel entry
+/// point of the crate.
 pub trait Comparator {
     fn compare(&self, other: &Self) -> Ordering;
 }
 
-/// A cloning trait for moving objects when they are mutable.
+/// A cloning trait for duplicating objects. Sorting never needs it, since
+/// elements are only swapped around, but it is kept for code which has to hold
+/// on to copies of elements.
 pub trait Copier {
     fn copy(&self) -> Self;
 }
 
-fn partition_gen<T: Comparator + Copier>(arr: &mut Vec<T>, low: usize, high: usize) -> usize {
-    let pivot: T = T::copy(&arr[high - 1]);
-    let mut idx = low;
+/// Every totally-ordered type is a comparator which follows its `Ord`
+/// implementation. This covers integers, `char`, `bool`, `String`, `str`
+/// references, and `Option`, `Box`, `Rc`, `Arc`, tuples and arrays of
+/// totally-ordered types, so those can be sorted out of the box.
+impl<T: Ord + ?Sized> Comparator for T {
+    fn compare(&self, other: &Self) -> Ordering {
+        self.cmp(other)
+    }
+}
 
-    for j in low..high {
-        if T::compare(&arr[j], &pivot) == Ordering::Less {
-            arr.swap(idx, j);
-            idx = idx + 1;
-        }
+/// Every clonable type is a copier which follows its `Clone` implementation.
+/// Shared pointers (`&T`, `Rc<T>`, `Arc<T>`) copy the pointer and not the
+/// pointee.
+impl<T: Clone> Copier for T {
+    fn copy(&self) -> Self {
+        self.clone()
     }
-    idx
 }
 
-fn quicksort_gen<'a, T: Comparator + Copier>(
-    arr: &'a mut Vec<T>,
-    low: usize,
-    high: usize,
-) -> &'a Vec<T> {
-    if low >= usize::MIN && high >= usize::MIN {
-        if low < high {
-            let mid = partition_gen(arr, low, high);
-            quicksort_gen(arr, low, mid - 1);
-            quicksort_gen(arr, mid, high);
+/// Sorts a slice of generic type, which must define a comparator. Elements
+/// are only ever swapped, never copied.
+pub fn sort_gen<T: Comparator>(arr: &mut [T]) -> &[T] {
+    sort_gen_by(arr, T::compare)
+}
+
+/// Sorts a slice of generic type, which must define a comparator, and returns
+/// the work done, see [`SortStats`].
+pub fn sort_gen_stats<T: Comparator>(arr: &mut [T]) -> SortStats {
+    Sorter::new().sort_gen_stats(arr)
+}
+
+/// Sorts elements of generic type lying in `range` of a slice, leaving the
+/// rest of the slice untouched. Returns the sorted sub-slice.
+///
+/// Panics if the range is out of bounds of the slice.
+pub fn sort_gen_range<T: Comparator, R: RangeBounds<usize>>(arr: &mut [T], range: R) -> &[T] {
+    sort_gen(&mut arr[bounds(range)])
+}
+
+/// Sorts a slice of generic type with a comparator closure, instead of the
+/// `Comparator` implementation of the type. This allows sorting the same type
+/// in different orders.
+pub fn sort_gen_by<T, F>(arr: &mut [T], compare: F) -> &[T]
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    Sorter::new().sort_gen_by(arr, compare)
+}
+
+/// Sorts a slice of generic type by comparing the keys extracted from each
+/// element. The key is extracted on every comparison, see
+/// [`sort_gen_by_cached_key`] for expensive key functions.
+pub fn sort_gen_by_key<T, K, F>(arr: &mut [T], mut key: F) -> &[T]
+where
+    K: Comparator,
+    F: FnMut(&T) -> K,
+{
+    sort_gen_by(arr, |a, b| key(a).compare(&key(b)))
+}
+
+/// Sorts a slice of generic type by comparing the keys extracted from each
+/// element. Every key is extracted exactly once and the slice is then
+/// rearranged in place (decorate-sort-undecorate), which pays off when the key
+/// function is expensive.
+pub fn sort_gen_by_cached_key<T, K, F>(arr: &mut [T], key: F) -> &[T]
+where
+    K: Comparator,
+    F: FnMut(&T) -> K,
+{
+    let keys: Vec<K> = arr.iter().map(key).collect();
+    let mut indices: Vec<usize> = (0..arr.len()).collect();
+    sort_gen_by(&mut indices, |&a, &b| keys[a].compare(&keys[b]));
+    permute(arr, &mut indices);
+    arr
+}
+
+/// Rearranges `arr` in place so that position `i` receives the element
+/// originally at `indices[i]`. `indices` must be a permutation of the indices
+/// of `arr` and is overwritten.
+pub(crate) fn permute<T>(arr: &mut [T], indices: &mut [usize]) {
+    // The element for position `i` may already have been swapped away, in
+    // which case it is found by following the earlier swaps.
+    for i in 0..arr.len() {
+        let mut idx = indices[i];
+        while idx < i {
+            idx = indices[idx];
         }
+        indices[i] = idx;
+        arr.swap(i, idx);
     }
-    arr
 }
 
-/// Sorts a vector of generic type, which must define a comparator and copy
-/// trait.
-pub fn sort_gen<'a, T: Comparator + Copier>(arr: &'a mut Vec<T>) -> &'a Vec<T> {
-    quicksort_gen(arr, usize::MIN, arr.len())
+/// Sorts a slice of generic type using several threads. Elements which
+/// compare equal may end up in a different order than with [`sort_gen`].
+pub fn par_sort_gen<T: Comparator + Send>(arr: &mut [T]) -> &[T] {
+    Sorter::new().par_sort_gen(arr)
+}
+
+/// Sorts a slice of generic type with a comparator closure using several
+/// threads.
+pub fn par_sort_gen_by<T, F>(arr: &mut [T], compare: F) -> &[T]
+where
+    T: Send,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    Sorter::new().par_sort_gen_by(arr, compare)
+}
+
+/// Sorts a slice of generic type with a parallel sample sort, meant for very
+/// large slices.
+pub fn par_sample_sort_gen<T: Comparator + Send + Sync>(arr: &mut [T]) -> &[T] {
+    Sorter::new().par_sample_sort_gen(arr)
 }
 
 #[cfg(test)]
+// The original tests are kept as they were written.
+#[allow(clippy::needless_return, clippy::needless_range_loop)]
 mod tests {
     use super::*;
     use rand::Rng;
@@ -123,14 +249,23 @@ mod tests {
     fn test_quicksort() {
         let mut rng = rand::thread_rng();
 
-        let mut numbers: Vec<i64> = (0..25).map(|_| rng.gen_range(1..3001)).collect();
+        let original: Vec<i64> = (0..25).map(|_| rng.gen_range(1..3001)).collect();
+        let mut numbers = original.clone();
         sort(&mut numbers);
 
-        let mut elem = numbers[0];
-        for idx in 1..numbers.len() {
-            assert!(elem < numbers[idx]);
-            elem = numbers[idx];
-        }
+        assert!(is_sorted_gen(&numbers));
+        assert_eq!(verify_sorted_permutation(&original, &numbers), Ok(()));
+    }
+
+    #[test]
+    fn test_quicksort_duplicates() {
+        let mut rng = rand::thread_rng();
+
+        let original: Vec<i64> = (0..1_000).map(|_| rng.gen_range(1..10)).collect();
+        let mut numbers = original.clone();
+        sort(&mut numbers);
+
+        assert_eq!(verify_sorted_permutation(&original, &numbers), Ok(()));
     }
 
     #[test]
@@ -149,4 +284,467 @@ mod tests {
             elem = Node::copy(&nodes[idx]);
         }
     }
+
+    #[test]
+    fn test_sort_slices() {
+        let mut arr = [5, -3, 9, 0, 2];
+        sort(&mut arr);
+        assert_eq!(arr, [-3, 0, 2, 5, 9]);
+
+        let mut boxed: Box<[i64]> = vec![3, 1, 2].into_boxed_slice();
+        sort(&mut boxed);
+        assert_eq!(&*boxed, &[1, 2, 3]);
+
+        let mut empty: [i64; 0] = [];
+        assert!(sort(&mut empty).is_empty());
+    }
+
+    #[test]
+    fn test_sort_range() {
+        let mut numbers = vec![9, 8, 7, 6, 5, 4, 3];
+        assert_eq!(sort_range(&mut numbers, 2..5), &[5, 6, 7]);
+        assert_eq!(numbers, vec![9, 8, 5, 6, 7, 4, 3]);
+
+        sort_range(&mut numbers, ..);
+        assert_eq!(numbers, vec![3, 4, 5, 6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn test_generic_sort_range() {
+        let mut nodes = vec![
+            Node::new(9, String::from("init")),
+            Node::new(4, String::from("kobj2")),
+            Node::new(2, String::from("kobj")),
+            Node::new(1, String::from("systemd")),
+        ];
+        sort_gen_range(&mut nodes, 1..);
+
+        let pids: Vec<u64> = nodes.iter().map(|n| n.pid).collect();
+        assert_eq!(pids, vec![9, 1, 2, 4]);
+    }
+
+    #[test]
+    fn test_std_types() {
+        let mut strings = vec![String::from("b"), String::from("c"), String::from("a")];
+        sort_gen(&mut strings);
+        assert_eq!(strings, vec!["a", "b", "c"]);
+
+        let mut refs = vec!["kobj", "systemd", "init"];
+        sort_gen(&mut refs);
+        assert_eq!(refs, vec!["init", "kobj", "systemd"]);
+
+        let mut tuples = vec![(2, 'a'), (1, 'z'), (2, 'A')];
+        sort_gen(&mut tuples);
+        assert_eq!(tuples, vec![(1, 'z'), (2, 'A'), (2, 'a')]);
+
+        let mut arrays = vec![[3, 1], [1, 2], [1, 1]];
+        sort_gen(&mut arrays);
+        assert_eq!(arrays, vec![[1, 1], [1, 2], [3, 1]]);
+
+        let mut options = vec![Some(3), None, Some(-1)];
+        sort_gen(&mut options);
+        assert_eq!(options, vec![None, Some(-1), Some(3)]);
+    }
+
+    #[test]
+    fn test_std_pointers() {
+        use std::rc::Rc;
+        use std::sync::Arc;
+
+        let mut boxes: Vec<Box<u8>> = vec![Box::new(7), Box::new(3), Box::new(5)];
+        sort_gen(&mut boxes);
+        assert_eq!(boxes, vec![Box::new(3), Box::new(5), Box::new(7)]);
+
+        let mut rcs: Vec<Rc<u8>> = vec![Rc::new(7), Rc::new(3), Rc::new(5)];
+        sort_gen(&mut rcs);
+        assert_eq!(rcs, vec![Rc::new(3), Rc::new(5), Rc::new(7)]);
+
+        let mut arcs: Vec<Arc<u8>> = vec![Arc::new(7), Arc::new(3), Arc::new(5)];
+        sort_gen(&mut arcs);
+        assert_eq!(arcs, vec![Arc::new(3), Arc::new(5), Arc::new(7)]);
+
+        let (a, b, c) = (3, 1, 2);
+        let mut borrowed = vec![&a, &b, &c];
+        sort_gen(&mut borrowed);
+        assert_eq!(borrowed, vec![&1, &2, &3]);
+    }
+
+    fn processes() -> Vec<Node> {
+        vec![
+            Node::new(4, String::from("kobj2")),
+            Node::new(2, String::from("systemd")),
+            Node::new(1, String::from("kthreadd")),
+            Node::new(3, String::from("init")),
+        ]
+    }
+
+    #[test]
+    fn test_sort_gen_by() {
+        let mut nodes = processes();
+        sort_gen_by(&mut nodes, |a, b| b.pid.cmp(&a.pid));
+        let pids: Vec<u64> = nodes.iter().map(|n| n.pid).collect();
+        assert_eq!(pids, vec![4, 3, 2, 1]);
+
+        sort_gen_by(&mut nodes, |a, b| a.name.cmp(&b.name));
+        let names: Vec<&str> = nodes.iter().map(|n| n.name.as_str()).collect();
+        assert_eq!(names, vec!["init", "kobj2", "kthreadd", "systemd"]);
+    }
+
+    #[test]
+    fn test_sort_gen_by_key() {
+        let mut nodes = processes();
+        sort_gen_by_key(&mut nodes, |n| n.name.len());
+        let pids: Vec<u64> = nodes.iter().map(|n| n.pid).collect();
+        assert_eq!(pids[0], 3);
+        assert_eq!(pids[3], 1);
+
+        let mut nodes = processes();
+        let mut calls = 0;
+        sort_gen_by_cached_key(&mut nodes, |n| {
+            calls += 1;
+            n.name.clone()
+        });
+        let names: Vec<&str> = nodes.iter().map(|n| n.name.as_str()).collect();
+        assert_eq!(names, vec!["init", "kobj2", "kthreadd", "systemd"]);
+        assert_eq!(calls, 4);
+    }
+
+    #[test]
+    fn test_sort_gen_by_cached_key_permutation() {
+        let mut rng = rand::thread_rng();
+        let mut numbers: Vec<i64> = (0..200).map(|_| rng.gen_range(-50..50)).collect();
+        let mut expected = numbers.clone();
+        expected.sort_by_key(|x| x.abs());
+
+        sort_gen_by_cached_key(&mut numbers, |x| x.abs());
+        let keys: Vec<i64> = numbers.iter().map(|x| x.abs()).collect();
+        let expected_keys: Vec<i64> = expected.iter().map(|x| x.abs()).collect();
+        assert_eq!(keys, expected_keys);
+
+        numbers.sort_unstable();
+        expected.sort_unstable();
+        assert_eq!(numbers, expected);
+    }
+
+    #[test]
+    fn test_sort_gen_checked() {
+        // `Node::compare` never returns `Equal`, which only goes unnoticed as
+        // long as no two nodes are identical.
+        let mut nodes = processes();
+        let pids: Vec<u64> = sort_gen_checked(&mut nodes)
+            .unwrap()
+            .iter()
+            .map(|n| n.pid)
+            .collect();
+        assert_eq!(pids, vec![1, 2, 3, 4]);
+
+        nodes.push(Node::new(3, String::from("init")));
+        let error = sort_gen_checked(&mut nodes).unwrap_err();
+        assert!(matches!(
+            error,
+            ComparatorError::NotAntisymmetric {
+                first: 2,
+                second: 4,
+                ..
+            } | ComparatorError::NotAntisymmetric {
+                first: 4,
+                second: 2,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_comparator_laws() {
+        // A node compares less than itself.
+        let violation = check_comparator_laws(processes()).unwrap_err();
+        assert_eq!(violation.law, Law::Reflexivity);
+        assert_eq!(violation.counterexample[0].pid, 4);
+
+        assert!(check_comparator_laws_with(|rng| rng.gen_range(-5i64..5), 20).is_ok());
+    }
+
+    #[test]
+    fn test_non_clonable() {
+        /// A handle which must never be duplicated.
+        #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+        struct Handle(u32);
+
+        let algorithms = [Algorithm::Pdq, Algorithm::Quicksort, Algorithm::DualPivot];
+        for algorithm in algorithms.iter() {
+            for &three_way in [false, true].iter() {
+                let mut handles: Vec<Handle> = [5, 3, 9, 3, 1].iter().map(|&n| Handle(n)).collect();
+                Sorter::new()
+                    .algorithm(*algorithm)
+                    .three_way(three_way)
+                    .sort_gen(&mut handles);
+                let ids: Vec<u32> = handles.iter().map(|h| h.0).collect();
+                assert_eq!(ids, vec![1, 3, 3, 5, 9]);
+            }
+        }
+
+        let mut locks: Vec<std::sync::Mutex<u32>> =
+            (0..10).rev().map(std::sync::Mutex::new).collect();
+        sort_gen_by_key(&mut locks, |m| *m.lock().unwrap());
+        let values: Vec<u32> = locks.iter().map(|m| *m.lock().unwrap()).collect();
+        assert_eq!(values, (0..10).collect::<Vec<u32>>());
+    }
+
+    mod derive {
+        use crate::{sort_gen, Comparator, Copier};
+        // With the `derive` feature the macros come in with the traits.
+        #[cfg(not(feature = "derive"))]
+        use quicksort_gen_derive::{Comparator, Copier};
+        use std::cmp::Ordering;
+
+        #[derive(Comparator, Copier, Debug, PartialEq)]
+        struct Node {
+            #[compare(order = 1)]
+            pid: u64,
+            #[compare(order = 2, desc)]
+            name: String,
+            #[compare(skip)]
+            cpu_time: f64,
+        }
+
+        #[derive(Comparator, Copier, Debug, PartialEq)]
+        struct Pair<'a, T: Clone, U = u8>(T, &'a str, #[compare(skip)] U)
+        where
+            T: std::fmt::Debug;
+
+        #[derive(Comparator, Copier)]
+        struct Empty;
+
+        #[derive(Comparator, Copier, Debug, PartialEq)]
+        struct Version {
+            major: u8,
+            #[compare(order = -1)]
+            epoch: u8,
+        }
+
+        /// Equal signs nested in bounds must not be taken for defaults.
+        #[derive(Comparator, Copier, Debug, PartialEq)]
+        struct Stream<I: Iterator<Item = u8>, const N: usize = 4> {
+            id: u8,
+            source: I,
+        }
+
+        #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+        struct Countdown(u8);
+
+        impl Iterator for Countdown {
+            type Item = u8;
+
+            fn next(&mut self) -> Option<u8> {
+                self.0 = self.0.checked_sub(1)?;
+                Some(self.0)
+            }
+        }
+
+        fn node(pid: u64, name: &str, cpu_time: f64) -> Node {
+            Node {
+                pid,
+                name: String::from(name),
+                cpu_time,
+            }
+        }
+
+        #[test]
+        fn test_derive_comparator() {
+            let mut nodes = vec![
+                node(2, "kobj", 0.5),
+                node(1, "init", 2.0),
+                node(2, "systemd", 1.5),
+            ];
+            sort_gen(&mut nodes);
+            let names: Vec<&str> = nodes.iter().map(|n| n.name.as_str()).collect();
+            assert_eq!(names, vec!["init", "systemd", "kobj"]);
+
+            // Skipped fields do not take part, so equal records are Equal.
+            assert_eq!(
+                node(3, "bash", 1.0).compare(&node(3, "bash", 9.0)),
+                Ordering::Equal
+            );
+            assert_eq!(Empty.compare(&Empty), Ordering::Equal);
+
+            // Negative orders come before positive and missing ones.
+            let version = |epoch, major| Version { major, epoch };
+            assert_eq!(version(1, 0).compare(&version(0, 9)), Ordering::Greater);
+
+            // The skipped field needs no comparator.
+            assert_eq!(
+                Pair(1, "a", 0.5).compare(&Pair(1, "a", f64::NAN)),
+                Ordering::Equal
+            );
+
+            let mut pairs = vec![Pair(2, "b", 0), Pair(1, "z", 9), Pair(2, "a", 5)];
+            sort_gen(&mut pairs);
+            assert_eq!(
+                pairs,
+                vec![Pair(1, "z", 9), Pair(2, "a", 5), Pair(2, "b", 0)]
+            );
+        }
+
+        #[test]
+        fn test_derive_copier() {
+            let original = node(7, "sshd", 0.25);
+            assert_eq!(original.copy(), original);
+
+            let pair: Pair<'_, i32, u16> = Pair(4, "kobj", 300);
+            assert_eq!(pair.copy(), pair);
+            let _ = Empty.copy();
+        }
+
+        #[test]
+        fn test_derive_associated_type_bounds() {
+            let stream = |id, left| Stream::<Countdown> {
+                id,
+                source: Countdown(left),
+            };
+            let mut streams = vec![stream(2, 0), stream(1, 9), stream(2, 3)];
+            sort_gen(&mut streams);
+            assert_eq!(streams, vec![stream(1, 9), stream(2, 0), stream(2, 3)]);
+            assert_eq!(streams[0].copy(), streams[0]);
+        }
+    }
+
+    mod panic_safety {
+        use crate::pivot::First;
+        use crate::*;
+        use rand::Rng;
+        use std::cmp::Ordering;
+        use std::panic::{self, AssertUnwindSafe};
+        use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+        /// An element which counts comparisons and drops, and whose
+        /// comparator panics on the `panic_at`-th call.
+        struct Tracked<'a> {
+            key: u32,
+            tag: usize,
+            calls: &'a AtomicUsize,
+            panic_at: usize,
+            drops: &'a AtomicUsize,
+        }
+
+        impl Comparator for Tracked<'_> {
+            fn compare(&self, other: &Self) -> Ordering {
+                if self.calls.fetch_add(1, Relaxed) == self.panic_at {
+                    panic!("comparator panicked on purpose");
+                }
+                self.key.cmp(&other.key)
+            }
+        }
+
+        impl Drop for Tracked<'_> {
+            fn drop(&mut self) {
+                self.drops.fetch_add(1, Relaxed);
+            }
+        }
+
+        /// Runs `sort` with comparators panicking after a random number of
+        /// calls, up to `max_calls` per element, and checks that no element is
+        /// lost, duplicated or dropped while sorting.
+        fn check<S: Fn(&mut [Tracked])>(max_calls: usize, sort: S) {
+            let mut rng = rand::thread_rng();
+            let mut panics = 0;
+            for &len in [0, 1, 15, 200, 3_000].iter() {
+                for round in 0..8 {
+                    let calls = AtomicUsize::new(0);
+                    let drops = AtomicUsize::new(0);
+                    let panic_at = rng.gen_range(0..=len * max_calls);
+                    let mut arr: Vec<Tracked> = (0..len)
+                        .map(|tag| Tracked {
+                            // Presorted input drives quicksort into its
+                            // heapsort fallback.
+                            key: if round == 0 {
+                                tag as u32
+                            } else {
+                                rng.gen_range(0..50)
+                            },
+                            tag,
+                            calls: &calls,
+                            panic_at,
+                            drops: &drops,
+                        })
+                        .collect();
+
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| sort(&mut arr)));
+                    panics += result.is_err() as usize;
+                    assert_eq!(drops.load(Relaxed), 0);
+                    let mut tags: Vec<usize> = arr.iter().map(|elem| elem.tag).collect();
+                    tags.sort_unstable();
+                    assert!(tags.iter().copied().eq(0..len));
+                    drop(arr);
+                    assert_eq!(drops.load(Relaxed), len);
+                }
+            }
+            assert!(panics > 0);
+        }
+
+        #[test]
+        fn test_sort_panic_safety() {
+            check(12, |arr| {
+                sort_gen(arr);
+            });
+            check(12, |arr| {
+                sort_gen_range(arr, arr.len() / 3..);
+            });
+            check(12, |arr| {
+                sort_gen_by_key(arr, |elem| {
+                    assert!(elem.calls.fetch_add(1, Relaxed) != elem.panic_at);
+                    elem.key
+                });
+            });
+            // Every key is extracted once.
+            check(1, |arr| {
+                sort_gen_by_cached_key(arr, |elem| {
+                    assert!(elem.calls.fetch_add(1, Relaxed) != elem.panic_at);
+                    elem.key
+                });
+            });
+        }
+
+        #[test]
+        fn test_sorter_panic_safety() {
+            let quicksort = Sorter::new().algorithm(Algorithm::Quicksort);
+            check(12, |arr| {
+                quicksort.sort_gen(arr);
+            });
+            check(12, |arr| {
+                quicksort.clone().pivot(First).sort_gen(arr);
+            });
+            check(12, |arr| {
+                quicksort.clone().three_way(true).sort_gen(arr);
+            });
+            check(12, |arr| {
+                Sorter::new().algorithm(Algorithm::DualPivot).sort_gen(arr);
+            });
+        }
+
+        #[test]
+        fn test_select_panic_safety() {
+            check(3, |arr| {
+                if !arr.is_empty() {
+                    select_nth_gen(arr, arr.len() / 2);
+                }
+            });
+            check(3, |arr| {
+                partial_sort_gen(arr, arr.len() / 4);
+            });
+            check(3, |arr| {
+                sort_range_of_ranks(arr, arr.len() / 4..arr.len() / 2);
+            });
+        }
+
+        #[test]
+        fn test_parallel_panic_safety() {
+            let sorter = Sorter::new().grain_size(100);
+            check(12, |arr| {
+                sorter.par_sort_gen(arr);
+            });
+            check(12, |arr| {
+                sorter.par_sample_sort_gen(arr);
+            });
+        }
+    }
 }
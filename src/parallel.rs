@@ -0,0 +1,342 @@
+//! Parallel sorting on scoped threads.
+//!
+//! The slice is partitioned on the calling thread, then both sides are sorted
+//! concurrently, one of them on a new scoped thread, until the parts are no
+//! longer than the grain size of the [`Sorter`]. Those are sorted with the
+//! sequential algorithm of the sorter. The result is the same as that of a
+//! sequential sort up to the order of elements which compare equal, since the
+//! partitions split across threads do not follow the chosen algorithm.
+//!
+//! For very large slices the first partitions run on a single thread and
+//! become the bottleneck. Sample sort avoids them: every element is assigned
+//! to one of many buckets in parallel, the elements are then moved to their
+//! buckets in a single sequential pass of swaps, and all buckets are sorted
+//! concurrently. Only that pass, which compares nothing, is left on one
+//! thread, at the cost of a `u32` bucket index per element.
+
+use crate::partition::{partition3_gen, partition_gen};
+use crate::pivot::PivotStrategy;
+use crate::quicksort::depth_limit;
+use crate::{sort_gen_by, Comparator, Sorter};
+use rand::seq::index;
+use std::cmp::Ordering;
+use std::thread;
+
+/// Number of sample elements drawn per bucket of a sample sort. Oversampling
+/// makes the bucket sizes even out.
+const OVERSAMPLING: usize = 32;
+
+/// Returns how many threads a parallel sort should keep busy, a few per core.
+fn thread_count() -> usize {
+    let cores = thread::available_parallelism().map_or(1, |n| n.get());
+    4 * cores.next_power_of_two()
+}
+
+/// Returns how many levels of partitions are split across threads. Every
+/// level doubles the number of threads, which is capped by [`thread_count`]
+/// so huge slices do not spawn thousands of threads.
+fn split_depth(len: usize) -> usize {
+    depth_limit(len).min(thread_count().trailing_zeros() as usize)
+}
+
+/// Returns the bucket of `elem`: the number of splitters not greater than it.
+fn bucket_of<T, F>(splitters: &[&T], elem: &T, compare: &F) -> u32
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    splitters.partition_point(|&splitter| compare(splitter, elem) != Ordering::Greater) as u32
+}
+
+/// Moves every element of `arr` to the bucket given by `buckets`, which must
+/// hold the bucket of every element, whose first index is given by `starts`.
+/// Only swaps elements around, and never compares them.
+fn distribute<T>(arr: &mut [T], buckets: &mut [u32], starts: &[usize]) {
+    let mut next = starts.to_vec();
+    for bucket in 0..next.len() {
+        let end = starts.get(bucket + 1).copied().unwrap_or(arr.len());
+        while next[bucket] < end {
+            let idx = next[bucket];
+            let target = buckets[idx] as usize;
+            if target == bucket {
+                next[bucket] += 1;
+            } else {
+                let dest = next[target];
+                arr.swap(idx, dest);
+                buckets.swap(idx, dest);
+                next[target] += 1;
+            }
+        }
+    }
+}
+
+impl<P: PivotStrategy + Clone + Send + Sync> Sorter<P> {
+    /// Sorts a slice of generic type using several threads. The result is
+    /// identical to that of [`Sorter::sort_gen`] up to the order of elements
+    /// which compare equal.
+    pub fn par_sort_gen<'a, T>(&self, arr: &'a mut [T]) -> &'a [T]
+    where
+        T: Comparator + Send,
+    {
+        self.par_sort_gen_by(arr, T::compare)
+    }
+
+    /// Sorts a slice of generic type with a comparator closure using several
+    /// threads. The closure is shared between threads, so it must be `Fn` and
+    /// `Sync`.
+    pub fn par_sort_gen_by<'a, T, F>(&self, arr: &'a mut [T], compare: F) -> &'a [T]
+    where
+        T: Send,
+        F: Fn(&T, &T) -> Ordering + Sync,
+    {
+        let depth = split_depth(arr.len());
+        self.par_quicksort(arr, self.pivot.clone(), &compare, depth);
+        arr
+    }
+
+    /// Sorts a slice of generic type with a parallel sample sort, which scales
+    /// better than [`Sorter::par_sort_gen`] on slices with hundreds of millions
+    /// of elements.
+    pub fn par_sample_sort_gen<'a, T>(&self, arr: &'a mut [T]) -> &'a [T]
+    where
+        T: Comparator + Send + Sync,
+    {
+        self.par_sample_sort_gen_by(arr, T::compare)
+    }
+
+    /// Sorts a slice of generic type with a comparator closure and a parallel
+    /// sample sort. Like [`Sorter::par_sort_gen_by`], elements which compare
+    /// equal may end up in a different order than with a sequential sort.
+    ///
+    /// Randomly drawn splitters cut the slice into buckets. Every element is
+    /// assigned to its bucket by a binary search over the splitters, in
+    /// parallel, then moved there in place by a sequential O(n) pass of
+    /// swaps on the calling thread, and finally all buckets are sorted
+    /// concurrently with the sequential algorithm of the sorter. Classifying
+    /// takes a `u32` per element of extra memory.
+    /// Slices too short to fill two buckets of the grain size are handed to
+    /// [`Sorter::par_sort_gen_by`].
+    pub fn par_sample_sort_gen_by<'a, T, F>(&self, arr: &'a mut [T], compare: F) -> &'a [T]
+    where
+        T: Send + Sync,
+        F: Fn(&T, &T) -> Ordering + Sync,
+    {
+        let len = arr.len();
+        let bucket_count = thread_count().min(len / self.grain_size);
+        if bucket_count < 2 {
+            return self.par_sort_gen_by(arr, compare);
+        }
+
+        let mut buckets = self.classify(arr, bucket_count, &compare);
+        let mut starts = vec![0; bucket_count];
+        for &bucket in buckets.iter() {
+            if let Some(start) = starts.get_mut(bucket as usize + 1) {
+                *start += 1;
+            }
+        }
+        for bucket in 1..bucket_count {
+            starts[bucket] += starts[bucket - 1];
+        }
+        distribute(arr, &mut buckets, &starts);
+        drop(buckets);
+
+        thread::scope(|scope| {
+            let mut rest = &mut *arr;
+            let mut offset = 0;
+            for &start in starts[1..].iter() {
+                let (bucket, tail) = rest.split_at_mut(start - offset);
+                offset = start;
+                rest = tail;
+                let compare = &compare;
+                scope.spawn(move || self.sort_gen_by(bucket, compare));
+            }
+            self.sort_gen_by(rest, &compare);
+        });
+        arr
+    }
+
+    /// Draws `OVERSAMPLING` random samples per bucket, picks evenly spaced
+    /// splitters among them and returns the bucket of every element of `arr`.
+    fn classify<T, F>(&self, arr: &[T], bucket_count: usize, compare: &F) -> Vec<u32>
+    where
+        T: Sync,
+        F: Fn(&T, &T) -> Ordering + Sync,
+    {
+        let len = arr.len();
+        let sample_len = (bucket_count * OVERSAMPLING).min(len);
+        let mut sample = index::sample(&mut rand::thread_rng(), len, sample_len).into_vec();
+        sort_gen_by(&mut sample, |&a, &b| compare(&arr[a], &arr[b]));
+        let splitters: Vec<&T> = (1..bucket_count)
+            .map(|bucket| &arr[sample[bucket * sample_len / bucket_count]])
+            .collect();
+
+        let mut buckets = vec![0; len];
+        let chunk = len.div_ceil(bucket_count);
+        thread::scope(|scope| {
+            for (elems, buckets) in arr.chunks(chunk).zip(buckets.chunks_mut(chunk)) {
+                let splitters = &splitters;
+                scope.spawn(move || {
+                    for (elem, bucket) in elems.iter().zip(buckets.iter_mut()) {
+                        *bucket = bucket_of(splitters, elem, compare);
+                    }
+                });
+            }
+        });
+        buckets
+    }
+
+    fn par_quicksort<T, F>(&self, arr: &mut [T], mut pivot: P, compare: &F, depth: usize)
+    where
+        T: Send,
+        F: Fn(&T, &T) -> Ordering + Sync,
+    {
+        if arr.len() <= self.grain_size || depth == 0 {
+            self.sort_gen_by(arr, compare);
+            return;
+        }
+
+        let mut compare_mut = compare;
+        let chosen = pivot.select(arr, &mut compare_mut);
+        let (lt, gt) = if self.three_way {
+            partition3_gen(arr, chosen, &mut compare_mut, &mut ())
+        } else {
+            let mid = partition_gen(arr, chosen, &mut compare_mut, &mut ());
+            (mid, mid + 1)
+        };
+
+        let (left, right) = arr.split_at_mut(gt);
+        let left = &mut left[..lt];
+        let right_pivot = pivot.clone();
+        thread::scope(|scope| {
+            scope.spawn(|| self.par_quicksort(right, right_pivot, compare, depth - 1));
+            self.par_quicksort(left, pivot, compare, depth - 1);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pivot::{Last, Random};
+    use crate::Algorithm;
+    use rand::Rng;
+
+    fn random(len: usize, range: i64) -> Vec<i64> {
+        let mut rng = rand::thread_rng();
+        (0..len).map(|_| rng.gen_range(0..range)).collect()
+    }
+
+    #[test]
+    fn test_par_sort_gen() {
+        for &range in [10, 1_000_000].iter() {
+            let mut numbers = random(200_000, range);
+            let mut expected = numbers.clone();
+            Sorter::new().sort_gen(&mut expected);
+
+            Sorter::new().grain_size(1_000).par_sort_gen(&mut numbers);
+            assert_eq!(numbers, expected);
+        }
+    }
+
+    #[test]
+    fn test_par_sort_gen_settings() {
+        let numbers = random(50_000, 1_000);
+        let mut expected = numbers.clone();
+        expected.sort_unstable();
+
+        let sorters = [
+            Sorter::new().grain_size(0),
+            Sorter::new().three_way(true).grain_size(100),
+            Sorter::new().algorithm(Algorithm::DualPivot),
+        ];
+        for sorter in sorters.iter() {
+            let mut numbers = numbers.clone();
+            sorter.par_sort_gen(&mut numbers);
+            assert_eq!(numbers, expected);
+        }
+
+        let mut copy = numbers.clone();
+        Sorter::new().pivot(Last).par_sort_gen(&mut copy);
+        assert_eq!(copy, expected);
+
+        let mut copy = numbers.clone();
+        Sorter::new()
+            .pivot(Random::seeded(9))
+            .par_sort_gen_by(&mut copy, |a, b| b.cmp(a));
+        expected.reverse();
+        assert_eq!(copy, expected);
+    }
+
+    #[test]
+    fn test_par_sort_equal_keys() {
+        // Pairs with equal keys compare equal but can be told apart, so only
+        // the keys are in the same order as after a sequential sort.
+        let mut rng = rand::thread_rng();
+        let pairs: Vec<(u8, u32)> = (0..100_000).map(|n| (rng.gen(), n)).collect();
+        let by_key = |a: &(u8, u32), b: &(u8, u32)| a.0.cmp(&b.0);
+        let mut expected = pairs.clone();
+        Sorter::new().sort_gen_by(&mut expected, by_key);
+        let keys = |pairs: &[(u8, u32)]| pairs.iter().map(|p| p.0).collect::<Vec<_>>();
+
+        let mut parallel = pairs.clone();
+        Sorter::new()
+            .grain_size(1_000)
+            .par_sort_gen_by(&mut parallel, by_key);
+        let mut sampled = pairs.clone();
+        Sorter::new()
+            .grain_size(1_000)
+            .par_sample_sort_gen_by(&mut sampled, by_key);
+
+        let mut all = pairs;
+        all.sort_unstable();
+        for mut sorted in [parallel, sampled] {
+            assert_eq!(keys(&sorted), keys(&expected));
+            sorted.sort_unstable();
+            assert_eq!(sorted, all);
+        }
+    }
+
+    #[test]
+    fn test_distribute() {
+        let mut letters = vec!['c', 'a', 'b', 'c', 'a', 'b', 'a'];
+        let mut buckets: Vec<u32> = letters.iter().map(|&c| c as u32 - 'a' as u32).collect();
+        distribute(&mut letters, &mut buckets, &[0, 3, 5]);
+        assert_eq!(letters, vec!['a', 'a', 'a', 'b', 'b', 'c', 'c']);
+        assert_eq!(buckets, vec![0, 0, 0, 1, 1, 2, 2]);
+    }
+
+    #[test]
+    fn test_par_sample_sort_gen() {
+        for &range in [3, 1_000, 1_000_000].iter() {
+            let mut numbers = random(100_000, range);
+            let mut expected = numbers.clone();
+            Sorter::new().sort_gen(&mut expected);
+
+            Sorter::new()
+                .grain_size(1_000)
+                .par_sample_sort_gen(&mut numbers);
+            assert_eq!(numbers, expected);
+        }
+
+        // Too short to fill two buckets.
+        let mut numbers = random(100, 50);
+        let mut expected = numbers.clone();
+        expected.sort_unstable();
+        Sorter::new().par_sample_sort_gen(&mut numbers);
+        assert_eq!(numbers, expected);
+    }
+
+    #[test]
+    fn test_par_sample_sort_gen_by() {
+        let mut words: Vec<String> = random(20_000, 5_000)
+            .iter()
+            .map(|n| n.to_string())
+            .collect();
+        let mut expected = words.clone();
+        expected.sort_unstable_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+
+        Sorter::new()
+            .grain_size(500)
+            .par_sample_sort_gen_by(&mut words, |a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+        assert_eq!(words, expected);
+    }
+}
@@ -0,0 +1,217 @@
+//! Partitioning of slices, the building block of every algorithm in this
+//! crate.
+//!
+//! All partitions work in place by swapping elements, and compare against the
+//! pivot where it lies in the slice, so elements are never copied.
+
+use crate::stats::Recorder;
+use crate::Comparator;
+use std::cmp::Ordering;
+
+/// Partitions `arr` around the element at index `pivot` and returns the final
+/// position of the pivot. Elements before it compare less than the pivot and
+/// elements after it do not.
+///
+/// The pivot is parked at the end of the slice and compared in place, so no
+/// element is ever copied.
+pub(crate) fn partition_gen<T, F, S>(
+    arr: &mut [T],
+    pivot: usize,
+    compare: &mut F,
+    stats: &mut S,
+) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+    S: Recorder,
+{
+    let high = arr.len() - 1;
+    stats.swap(arr, pivot, high);
+    let mut idx = 0;
+
+    for j in 0..high {
+        if compare(&arr[j], &arr[high]) == Ordering::Less {
+            stats.swap(arr, idx, j);
+            idx += 1;
+        }
+    }
+    stats.swap(arr, idx, high);
+    idx
+}
+
+/// Partitions `arr` around the element at index `pivot` into three groups,
+/// using Dijkstra's Dutch national flag scheme. Returns `(lt, gt)` such that
+/// `arr[..lt]` compares less than the pivot, `arr[lt..gt]` compares equal to
+/// it and `arr[gt..]` compares greater.
+pub(crate) fn partition3_gen<T, F, S>(
+    arr: &mut [T],
+    pivot: usize,
+    compare: &mut F,
+    stats: &mut S,
+) -> (usize, usize)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    S: Recorder,
+{
+    // The pivot is parked at the front and the rest is partitioned against it
+    // in place, then the pivot joins the elements equal to it.
+    stats.swap(arr, 0, pivot);
+    let (head, rest) = arr.split_at_mut(1);
+    let pivot = &head[0];
+    let (mut lt, mut idx, mut gt) = (0, 0, rest.len());
+
+    while idx < gt {
+        match compare(&rest[idx], pivot) {
+            Ordering::Less => {
+                stats.swap(rest, lt, idx);
+                lt += 1;
+                idx += 1;
+            }
+            Ordering::Greater => {
+                gt -= 1;
+                stats.swap(rest, idx, gt);
+            }
+            Ordering::Equal => idx += 1,
+        }
+    }
+    stats.swap(arr, 0, lt);
+    (lt, gt + 1)
+}
+
+/// Reorders a slice such that every element satisfying `pred` comes before
+/// every element which does not. Returns the number of elements satisfying
+/// `pred`, which is the index of the first element not satisfying it.
+///
+/// The relative order of elements is not preserved, and `pred` is called
+/// exactly once per element.
+pub fn partition_by<T, P>(arr: &mut [T], mut pred: P) -> usize
+where
+    P: FnMut(&T) -> bool,
+{
+    let mut left = 0;
+    let mut right = arr.len();
+    loop {
+        while left < right && pred(&arr[left]) {
+            left += 1;
+        }
+        if left == right {
+            return left;
+        }
+        // `arr[left]` does not satisfy `pred`, find an element which does
+        // from the right end.
+        loop {
+            right -= 1;
+            if right == left {
+                return left;
+            }
+            if pred(&arr[right]) {
+                break;
+            }
+        }
+        arr.swap(left, right);
+        left += 1;
+    }
+}
+
+/// Partitions a slice of generic type around the element at index `pivot`.
+/// Returns the final index of the pivot: elements before it are less than the
+/// pivot and elements after it are not.
+///
+/// Panics if `pivot` is out of bounds of the slice.
+pub fn partition_around<T: Comparator>(arr: &mut [T], pivot: usize) -> usize {
+    partition_around_by(arr, pivot, T::compare)
+}
+
+/// Partitions a slice of generic type around the element at index `pivot`,
+/// using a comparator closure. See [`partition_around`].
+pub fn partition_around_by<T, F>(arr: &mut [T], pivot: usize, mut compare: F) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    check_pivot(arr, pivot);
+    partition_gen(arr, pivot, &mut compare, &mut ())
+}
+
+/// Partitions a slice of generic type around the element at index `pivot`
+/// into three groups. Returns `(lt, gt)` such that `arr[..lt]` is less than the
+/// pivot, `arr[lt..gt]` is equal to it and `arr[gt..]` is greater.
+///
+/// Panics if `pivot` is out of bounds of the slice.
+pub fn partition_three_way<T: Comparator>(arr: &mut [T], pivot: usize) -> (usize, usize) {
+    partition_three_way_by(arr, pivot, T::compare)
+}
+
+/// Partitions a slice of generic type around the element at index `pivot`
+/// into three groups, using a comparator closure. See
+/// [`partition_three_way`].
+pub fn partition_three_way_by<T, F>(arr: &mut [T], pivot: usize, mut compare: F) -> (usize, usize)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    check_pivot(arr, pivot);
+    partition3_gen(arr, pivot, &mut compare, &mut ())
+}
+
+fn check_pivot<T>(arr: &[T], pivot: usize) {
+    assert!(
+        pivot < arr.len(),
+        "pivot {} out of bounds of a slice of length {}",
+        pivot,
+        arr.len()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn test_partition_by() {
+        let mut rng = rand::thread_rng();
+        for len in 0..50 {
+            let mut numbers: Vec<i64> = (0..len).map(|_| rng.gen_range(0..10)).collect();
+            let evens = numbers.iter().filter(|&&n| n % 2 == 0).count();
+            let mut calls = 0;
+            let mid = partition_by(&mut numbers, |&n| {
+                calls += 1;
+                n % 2 == 0
+            });
+            assert_eq!(mid, evens);
+            assert_eq!(calls, len);
+            assert!(numbers[..mid].iter().all(|&n| n % 2 == 0));
+            assert!(numbers[mid..].iter().all(|&n| n % 2 == 1));
+        }
+    }
+
+    #[test]
+    fn test_partition_around() {
+        let mut numbers = vec![4, 8, 1, 9, 4, 2];
+        let mid = partition_around(&mut numbers, 0);
+        assert_eq!(mid, 2);
+        assert_eq!(numbers[mid], 4);
+        assert!(numbers[..mid].iter().all(|&n| n < 4));
+        assert!(numbers[mid + 1..].iter().all(|&n| n >= 4));
+
+        let mut names = vec!["kobj", "systemd", "init", "bash"];
+        let mid = partition_around_by(&mut names, 0, |a, b| b.cmp(a));
+        assert_eq!(mid, 1);
+        assert_eq!(names[0], "systemd");
+    }
+
+    #[test]
+    fn test_partition_three_way() {
+        let mut numbers = vec![3, 1, 3, 5, 3, 0, 9, 3];
+        let (lt, gt) = partition_three_way(&mut numbers, 0);
+        assert_eq!((lt, gt), (2, 6));
+        assert!(numbers[..lt].iter().all(|&n| n < 3));
+        assert!(numbers[lt..gt].iter().all(|&n| n == 3));
+        assert!(numbers[gt..].iter().all(|&n| n > 3));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_partition_around_empty() {
+        let mut empty: [i64; 0] = [];
+        partition_around(&mut empty, 0);
+    }
+}
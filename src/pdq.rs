@@ -0,0 +1,409 @@
+//! Pattern-defeating quicksort (pdqsort), after Orson Peters.
+//!
+//! On top of plain quicksort it detects already partitioned and presorted
+//! slices, handles runs of elements equal to an earlier pivot in one linear
+//! pass, shuffles elements around when partitions become unbalanced, sorts
+//! short slices with insertion sort and falls back to heapsort after too many
+//! bad partitions.
+
+use crate::heapsort::heapsort_gen;
+use crate::stats::Recorder;
+use std::cmp::{self, Ordering};
+
+/// Slices of up to this length are sorted with insertion sort.
+const MAX_INSERTION: usize = 20;
+/// Maximum number of adjacent out-of-order pairs fixed by
+/// `partial_insertion_sort` before giving up.
+const MAX_STEPS: usize = 5;
+/// Slices shorter than this are not worth fixing up with insertion sort.
+const SHORTEST_SHIFTING: usize = 50;
+/// Slices of at least this length choose the pivot from a median of medians.
+const SHORTEST_MEDIAN_OF_MEDIANS: usize = 50;
+/// Maximum number of swaps `choose_pivot` can perform.
+const MAX_SWAPS: usize = 4 * 3;
+
+fn is_less<T, F>(compare: &mut F, a: &T, b: &T) -> bool
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    compare(a, b) == Ordering::Less
+}
+
+/// Moves the last element to the left until it is in sorted position,
+/// assuming the rest of `v` is sorted.
+fn shift_tail<T, F, S>(v: &mut [T], compare: &mut F, stats: &mut S)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    S: Recorder,
+{
+    let mut idx = v.len();
+    while idx > 1 && is_less(compare, &v[idx - 1], &v[idx - 2]) {
+        stats.swap(v, idx - 1, idx - 2);
+        idx -= 1;
+    }
+}
+
+/// Moves the first element to the right until it is in sorted position,
+/// assuming the rest of `v` is sorted.
+fn shift_head<T, F, S>(v: &mut [T], compare: &mut F, stats: &mut S)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    S: Recorder,
+{
+    let mut idx = 0;
+    while idx + 1 < v.len() && is_less(compare, &v[idx + 1], &v[idx]) {
+        stats.swap(v, idx, idx + 1);
+        idx += 1;
+    }
+}
+
+pub(crate) fn insertion_sort<T, F, S>(v: &mut [T], compare: &mut F, stats: &mut S)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    S: Recorder,
+{
+    for end in 2..=v.len() {
+        shift_tail(&mut v[..end], compare, stats);
+    }
+}
+
+/// Partially sorts `v` by shifting a few out-of-order elements around.
+/// Returns `true` if `v` ends up sorted.
+fn partial_insertion_sort<T, F, S>(v: &mut [T], compare: &mut F, stats: &mut S) -> bool
+where
+    F: FnMut(&T, &T) -> Ordering,
+    S: Recorder,
+{
+    let len = v.len();
+    let mut idx = 1;
+
+    for _ in 0..MAX_STEPS {
+        while idx < len && !is_less(compare, &v[idx], &v[idx - 1]) {
+            idx += 1;
+        }
+        if idx == len {
+            return true;
+        }
+        if len < SHORTEST_SHIFTING {
+            return false;
+        }
+
+        stats.swap(v, idx - 1, idx);
+        shift_tail(&mut v[..idx], compare, stats);
+        shift_head(&mut v[idx..], compare, stats);
+    }
+    false
+}
+
+/// Partitions `v` into elements less than `v[pivot]` followed by elements
+/// greater than or equal to it. Returns the final position of the pivot and
+/// whether `v` was already partitioned.
+fn partition<T, F, S>(v: &mut [T], pivot: usize, compare: &mut F, stats: &mut S) -> (usize, bool)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    S: Recorder,
+{
+    stats.swap(v, 0, pivot);
+    let (head, rest) = v.split_at_mut(1);
+    let pivot = &head[0];
+
+    let len = rest.len();
+    let mut left = 0;
+    while left < len && is_less(compare, &rest[left], pivot) {
+        left += 1;
+    }
+
+    // Branchless Lomuto scheme: every element is swapped into place and the
+    // boundary only advances past elements less than the pivot, which avoids
+    // mispredicted branches on random input. Recorders only count swaps of
+    // two distinct elements, so swapping in place is free in the stats.
+    let start = left;
+    for right in start..len {
+        let less = is_less(compare, &rest[right], pivot);
+        stats.swap(rest, left, right);
+        left += less as usize;
+    }
+    let was_partitioned = left == start;
+
+    stats.swap(v, 0, left);
+    (left, was_partitioned)
+}
+
+/// Partitions `v` into elements equal to `v[pivot]` followed by elements
+/// greater than it, assuming no element is less than the pivot. Returns the
+/// number of elements equal to the pivot.
+fn partition_equal<T, F, S>(v: &mut [T], pivot: usize, compare: &mut F, stats: &mut S) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+    S: Recorder,
+{
+    stats.swap(v, 0, pivot);
+    let (head, rest) = v.split_at_mut(1);
+    let pivot = &head[0];
+
+    let mut left = 0;
+    let mut right = rest.len();
+    loop {
+        while left < right && !is_less(compare, pivot, &rest[left]) {
+            left += 1;
+        }
+        while left < right && is_less(compare, pivot, &rest[right - 1]) {
+            right -= 1;
+        }
+        if left >= right {
+            break;
+        }
+        right -= 1;
+        stats.swap(rest, left, right);
+        left += 1;
+    }
+    left + 1
+}
+
+/// Scatters a few elements around to break patterns which cause unbalanced
+/// partitions.
+fn break_patterns<T, S: Recorder>(v: &mut [T], stats: &mut S) {
+    let len = v.len();
+    if len < 8 {
+        return;
+    }
+
+    // Xorshift seeded by the length, deterministic on purpose.
+    let mut random = len as u32;
+    let mut gen_u32 = || {
+        random ^= random << 13;
+        random ^= random >> 17;
+        random ^= random << 5;
+        random
+    };
+    let mut gen_usize = || {
+        if usize::BITS <= 32 {
+            gen_u32() as usize
+        } else {
+            (((gen_u32() as u64) << 32) | (gen_u32() as u64)) as usize
+        }
+    };
+
+    let modulus = len.next_power_of_two();
+    let pos = len / 4 * 2;
+    for i in 0..3 {
+        let mut other = gen_usize() & (modulus - 1);
+        if other >= len {
+            other -= len;
+        }
+        stats.swap(v, pos - 1 + i, other);
+    }
+}
+
+/// Chooses a pivot in `v` and returns its index along with `true` if `v` is
+/// likely already sorted. Slices which look descending are reversed.
+fn choose_pivot<T, F, S>(v: &mut [T], compare: &mut F, stats: &mut S) -> (usize, bool)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    S: Recorder,
+{
+    let len = v.len();
+    let mut a = len / 4;
+    let mut b = len / 4 * 2;
+    let mut c = len / 4 * 3;
+    let mut swaps = 0;
+
+    if len >= 8 {
+        let slice: &[T] = v;
+        let mut sort2 = |a: &mut usize, b: &mut usize| {
+            if is_less(compare, &slice[*b], &slice[*a]) {
+                std::mem::swap(a, b);
+                swaps += 1;
+            }
+        };
+        let mut sort3 = |a: &mut usize, b: &mut usize, c: &mut usize| {
+            sort2(a, b);
+            sort2(b, c);
+            sort2(a, b);
+        };
+
+        if len >= SHORTEST_MEDIAN_OF_MEDIANS {
+            let mut sort_adjacent = |a: &mut usize| {
+                let mid = *a;
+                sort3(&mut (mid - 1), a, &mut (mid + 1));
+            };
+            sort_adjacent(&mut a);
+            sort_adjacent(&mut b);
+            sort_adjacent(&mut c);
+        }
+        sort3(&mut a, &mut b, &mut c);
+    }
+
+    if swaps < MAX_SWAPS {
+        (b, swaps == 0)
+    } else {
+        // Every comparison was out of order, so the slice is likely
+        // descending. Reversing it makes it likely ascending.
+        stats.reverse(v);
+        (len - 1 - b, true)
+    }
+}
+
+/// Sorts `arr` with pdqsort in O(n log n) worst-case time.
+///
+/// The sort does not recurse. After each partition the longer side is pushed
+/// on an explicit stack and the shorter side is sorted first, so the stack
+/// never holds more than `log2(n)` ranges.
+pub(crate) fn pdqsort_gen<T, F, S>(arr: &mut [T], compare: &mut F, stats: &mut S)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    S: Recorder,
+{
+    // Every pending range remembers whether the element right before it is
+    // the pivot of an earlier partition, which is then not greater than any
+    // element of the range. `limit` is the number of unbalanced partitions
+    // still allowed before switching to heapsort.
+    let limit = usize::BITS - arr.len().leading_zeros();
+    let mut pending = vec![(0, arr.len(), limit, false, 1)];
+
+    while let Some((mut lo, mut hi, mut limit, mut has_pred, mut depth)) = pending.pop() {
+        let mut was_balanced = true;
+        let mut was_partitioned = true;
+
+        loop {
+            let (before, v) = arr[..hi].split_at_mut(lo);
+            let pred = if has_pred { before.last() } else { None };
+            let len = v.len();
+            if len <= MAX_INSERTION {
+                insertion_sort(v, compare, stats);
+                break;
+            }
+            if limit == 0 {
+                stats.fallback();
+                heapsort_gen(v, compare, stats);
+                break;
+            }
+            if !was_balanced {
+                break_patterns(v, stats);
+                limit -= 1;
+            }
+
+            let (pivot, likely_sorted) = choose_pivot(v, compare, stats);
+            if was_balanced
+                && was_partitioned
+                && likely_sorted
+                && partial_insertion_sort(v, compare, stats)
+            {
+                break;
+            }
+
+            // If the pivot equals the predecessor, it is the smallest element
+            // of `v`. Partitioning off every element equal to it leaves only
+            // greater elements, which helps a lot with many duplicates. The
+            // last of the equal elements becomes the new predecessor.
+            if let Some(pred) = pred {
+                if !is_less(compare, pred, &v[pivot]) {
+                    stats.partition(depth);
+                    depth += 1;
+                    lo += partition_equal(v, pivot, compare, stats);
+                    continue;
+                }
+            }
+
+            stats.partition(depth);
+            depth += 1;
+            let (mid, partitioned) = partition(v, pivot, compare, stats);
+            was_balanced = cmp::min(mid, len - mid) >= len / 8;
+            was_partitioned = partitioned;
+
+            let mid = lo + mid;
+            if mid - lo < hi - mid - 1 {
+                pending.push((mid + 1, hi, limit, true, depth));
+                hi = mid;
+            } else {
+                pending.push((lo, mid, limit, has_pred, depth));
+                lo = mid + 1;
+                has_pred = true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SortStats;
+    use rand::Rng;
+
+    fn check(mut numbers: Vec<i64>) {
+        let mut expected = numbers.clone();
+        expected.sort_unstable();
+        pdqsort_gen(&mut numbers, &mut i64::cmp, &mut ());
+        assert_eq!(numbers, expected);
+    }
+
+    #[test]
+    fn test_pdqsort() {
+        let mut rng = rand::thread_rng();
+        for len in (0..100).chain([1_000, 10_000]) {
+            check((0..len).map(|_| rng.gen_range(-1000..1000)).collect());
+            check((0..len).map(|_| rng.gen_range(0..3)).collect());
+        }
+    }
+
+    #[test]
+    fn test_pdqsort_patterns() {
+        let len = 10_000;
+        check((0..len).collect());
+        check((0..len).rev().collect());
+        check(vec![5; len as usize]);
+        check((0..len).map(|n| n % 100).collect());
+        check(
+            (0..len)
+                .map(|n| if n % 2 == 0 { n } else { len - n })
+                .collect(),
+        );
+    }
+
+    #[test]
+    fn test_pdqsort_presorted_is_linear() {
+        let mut numbers: Vec<i64> = (0..10_000).collect();
+        numbers.swap(3_000, 3_001);
+        let mut compares = 0;
+        pdqsort_gen(
+            &mut numbers,
+            &mut |a: &i64, b: &i64| {
+                compares += 1;
+                a.cmp(b)
+            },
+            &mut (),
+        );
+        assert!(numbers.windows(2).all(|w| w[0] <= w[1]));
+        assert!(compares < 3 * 10_000);
+    }
+
+    #[test]
+    fn test_partition_counts_real_swaps() {
+        // Both the pivot and the first element not less than it are swapped
+        // with themselves, which is not counted.
+        let mut numbers = [5, 1, 9, 2];
+        let mut stats = SortStats::default();
+        assert_eq!(
+            partition(&mut numbers, 0, &mut i64::cmp, &mut stats),
+            (2, false)
+        );
+        assert_eq!(numbers, [2, 1, 5, 9]);
+        assert_eq!(stats.swaps, 2);
+    }
+
+    #[test]
+    fn test_pdqsort_small_stack() {
+        let mut rng = rand::thread_rng();
+        let mut numbers: Vec<i64> = (0..100_000).map(|_| rng.gen()).collect();
+        let sorted = std::thread::Builder::new()
+            .stack_size(32 * 1024)
+            .spawn(move || {
+                pdqsort_gen(&mut numbers, &mut i64::cmp, &mut ());
+                numbers.windows(2).all(|w| w[0] <= w[1])
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert!(sorted);
+    }
+}
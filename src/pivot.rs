@@ -0,0 +1,198 @@
+//! Pivot selection strategies used while partitioning.
+//!
+//! Every partition step asks a [`PivotStrategy`] for the index of the element
+//! to partition around. Picking a fixed position (first or last) is cheap but
+//! makes already sorted input quadratic, while sampling strategies such as
+//! [`MedianOfThree`], [`Ninther`] or [`Random`] keep the partitions balanced on
+//! such input.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cmp::Ordering;
+
+/// Chooses the pivot of a non-empty slice.
+pub trait PivotStrategy {
+    /// Returns the index of the pivot in `arr`, comparing elements with
+    /// `compare`. `arr` is never empty.
+    fn select<T, F>(&mut self, arr: &[T], compare: &mut F) -> usize
+    where
+        F: FnMut(&T, &T) -> Ordering;
+}
+
+/// Always picks the first element.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct First;
+
+impl PivotStrategy for First {
+    fn select<T, F>(&mut self, _arr: &[T], _compare: &mut F) -> usize
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        0
+    }
+}
+
+/// Always picks the last element, which is the classic Lomuto choice.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Last;
+
+impl PivotStrategy for Last {
+    fn select<T, F>(&mut self, arr: &[T], _compare: &mut F) -> usize
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        arr.len() - 1
+    }
+}
+
+/// Picks the median of the first, middle and last elements.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MedianOfThree;
+
+impl PivotStrategy for MedianOfThree {
+    fn select<T, F>(&mut self, arr: &[T], compare: &mut F) -> usize
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        let len = arr.len();
+        median3(arr, 0, len / 2, len - 1, compare)
+    }
+}
+
+/// Picks Tukey's ninther: the median of three medians of three, sampled
+/// evenly over the slice. Falls back to [`MedianOfThree`] on short slices.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Ninther;
+
+/// Slices shorter than this do not have enough distinct samples for a ninther.
+const NINTHER_THRESHOLD: usize = 40;
+
+impl PivotStrategy for Ninther {
+    fn select<T, F>(&mut self, arr: &[T], compare: &mut F) -> usize
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        let len = arr.len();
+        if len < NINTHER_THRESHOLD {
+            return MedianOfThree.select(arr, compare);
+        }
+
+        let step = len / 8;
+        let mid = len / 2;
+        let last = len - 1;
+        let a = median3(arr, 0, step, 2 * step, compare);
+        let b = median3(arr, mid - step, mid, mid + step, compare);
+        let c = median3(arr, last - 2 * step, last - step, last, compare);
+        median3(arr, a, b, c, compare)
+    }
+}
+
+/// Picks an element uniformly at random.
+///
+/// The generator is seeded from the operating system by [`Random::new`], or
+/// from a fixed seed by [`Random::seeded`] for reproducible runs.
+#[derive(Clone, Debug)]
+pub struct Random {
+    rng: StdRng,
+}
+
+impl Random {
+    pub fn new() -> Self {
+        Self {
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    pub fn seeded(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Default for Random {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PivotStrategy for Random {
+    fn select<T, F>(&mut self, arr: &[T], _compare: &mut F) -> usize
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        self.rng.gen_range(0..arr.len())
+    }
+}
+
+/// Returns whichever of the indices `a`, `b` and `c` holds the median element.
+fn median3<T, F>(arr: &[T], a: usize, b: usize, c: usize, compare: &mut F) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let ab = compare(&arr[a], &arr[b]) == Ordering::Less;
+    let bc = compare(&arr[b], &arr[c]) == Ordering::Less;
+    if ab == bc {
+        return b;
+    }
+    let ac = compare(&arr[a], &arr[c]) == Ordering::Less;
+    if ab == ac {
+        c
+    } else {
+        a
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn select<P: PivotStrategy>(mut pivot: P, arr: &[i64]) -> usize {
+        pivot.select(arr, &mut i64::cmp)
+    }
+
+    #[test]
+    fn test_fixed_positions() {
+        let arr = [5, 1, 4, 2, 3];
+        assert_eq!(select(First, &arr), 0);
+        assert_eq!(select(Last, &arr), 4);
+        assert_eq!(select(First, &[7]), 0);
+        assert_eq!(select(Last, &[7]), 0);
+    }
+
+    #[test]
+    fn test_median_of_three() {
+        assert_eq!(select(MedianOfThree, &[1, 2, 3]), 1);
+        assert_eq!(select(MedianOfThree, &[3, 2, 1]), 1);
+        assert_eq!(select(MedianOfThree, &[2, 9, 1]), 0);
+        assert_eq!(select(MedianOfThree, &[1, 9, 2]), 2);
+        assert_eq!(select(MedianOfThree, &[4]), 0);
+        assert_eq!(select(MedianOfThree, &[4, 3]), 1);
+    }
+
+    #[test]
+    fn test_ninther() {
+        let sorted: Vec<i64> = (0..1000).collect();
+        assert_eq!(select(Ninther, &sorted), 500);
+
+        let reversed: Vec<i64> = (0..1000).rev().collect();
+        assert_eq!(select(Ninther, &reversed), 500);
+
+        assert_eq!(select(Ninther, &[2, 9, 1]), 0);
+    }
+
+    #[test]
+    fn test_random() {
+        let arr: Vec<i64> = (0..100).collect();
+        let first: Vec<usize> = {
+            let mut pivot = Random::seeded(7);
+            (0..10).map(|_| pivot.select(&arr, &mut i64::cmp)).collect()
+        };
+        let second: Vec<usize> = {
+            let mut pivot = Random::seeded(7);
+            (0..10).map(|_| pivot.select(&arr, &mut i64::cmp)).collect()
+        };
+        assert_eq!(first, second);
+        assert!(first.iter().all(|&idx| idx < arr.len()));
+    }
+}
@@ -0,0 +1,147 @@
+//! Quicksort engine shared by every sorting entry point of the crate.
+
+use crate::heapsort::heapsort_gen;
+use crate::partition::{partition3_gen, partition_gen};
+use crate::pivot::PivotStrategy;
+use crate::stats::Recorder;
+use std::cmp::Ordering;
+
+/// Returns the recursion depth allowed to introsort on a slice of length
+/// `len`, which is `2 * floor(log2(len))`.
+pub(crate) fn depth_limit(len: usize) -> usize {
+    len.checked_ilog2().map_or(0, |log| 2 * log as usize)
+}
+
+/// Sorts `arr`, switching to heapsort once the partitioning gets `limit`
+/// levels deep. A limit of `usize::MAX` disables the fallback in practice.
+/// With `three_way`, elements equal to the pivot are grouped around it and
+/// left out of further partitioning.
+///
+/// The sort does not recurse. After each partition the larger side is pushed
+/// on an explicit stack and the smaller side is sorted first, so the stack
+/// never holds more than `log2(n)` ranges whatever the pivots.
+pub(crate) fn quicksort_gen<T, P, F, S>(
+    arr: &mut [T],
+    pivot: &mut P,
+    compare: &mut F,
+    limit: usize,
+    three_way: bool,
+    stats: &mut S,
+) where
+    P: PivotStrategy,
+    F: FnMut(&T, &T) -> Ordering,
+    S: Recorder,
+{
+    let mut pending = vec![(0, arr.len(), limit, 1)];
+
+    while let Some((mut lo, mut hi, mut limit, mut depth)) = pending.pop() {
+        while hi - lo > 1 {
+            let v = &mut arr[lo..hi];
+            if limit == 0 {
+                stats.fallback();
+                heapsort_gen(v, compare, stats);
+                break;
+            }
+            limit -= 1;
+
+            stats.partition(depth);
+            let chosen = pivot.select(v, compare);
+            let (lt, gt) = if three_way {
+                partition3_gen(v, chosen, compare, stats)
+            } else {
+                let mid = partition_gen(v, chosen, compare, stats);
+                (mid, mid + 1)
+            };
+
+            let (left, right) = ((lo, lo + lt), (lo + gt, hi));
+            let (smaller, larger) = if lt < hi - lo - gt {
+                (left, right)
+            } else {
+                (right, left)
+            };
+            depth += 1;
+            pending.push((larger.0, larger.1, limit, depth));
+            (lo, hi) = smaller;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pivot::Last;
+
+    #[test]
+    fn test_depth_limit() {
+        assert_eq!(depth_limit(0), 0);
+        assert_eq!(depth_limit(1), 0);
+        assert_eq!(depth_limit(1023), 18);
+        assert_eq!(depth_limit(1024), 20);
+    }
+
+    #[test]
+    fn test_introsort_fallback() {
+        // Sorted input with the last element as pivot is the quadratic case,
+        // so the fallback must kick in well before partitioning gets deep.
+        let mut numbers: Vec<i64> = (0..10_000).collect();
+        let mut compares = 0;
+        let limit = depth_limit(numbers.len());
+        quicksort_gen(
+            &mut numbers,
+            &mut Last,
+            &mut |a: &i64, b: &i64| {
+                compares += 1;
+                a.cmp(b)
+            },
+            limit,
+            false,
+            &mut (),
+        );
+        assert!(numbers.windows(2).all(|w| w[0] <= w[1]));
+        assert!(compares < 4 * 10_000 * limit);
+    }
+
+    #[test]
+    fn test_three_way_duplicates() {
+        // With every element equal, the two-way partition degenerates while
+        // the three-way partition finishes after a single pass.
+        let mut numbers = vec![7i64; 10_000];
+        let mut compares = 0;
+        quicksort_gen(
+            &mut numbers,
+            &mut Last,
+            &mut |a: &i64, b: &i64| {
+                compares += 1;
+                a.cmp(b)
+            },
+            usize::MAX,
+            true,
+            &mut (),
+        );
+        assert_eq!(compares, 10_000 - 1);
+    }
+
+    #[test]
+    fn test_small_stack() {
+        // Quadratic input without the heapsort fallback partitions 5000 levels
+        // deep, which overflows a small stack unless the sort is iterative.
+        let sorted = std::thread::Builder::new()
+            .stack_size(32 * 1024)
+            .spawn(|| {
+                let mut numbers: Vec<i64> = (0..5_000).collect();
+                quicksort_gen(
+                    &mut numbers,
+                    &mut Last,
+                    &mut i64::cmp,
+                    usize::MAX,
+                    false,
+                    &mut (),
+                );
+                numbers.windows(2).all(|w| w[0] <= w[1])
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert!(sorted);
+    }
+}
@@ -0,0 +1,435 @@
+//! Selection of the k-th smallest element (quickselect).
+//!
+//! Selection partitions the slice like quicksort but only continues into the
+//! side holding the requested rank, which takes linear time on average. After
+//! a few partitions fail to shrink the slice by a quarter, the pivot is chosen
+//! by the median of medians instead, which bounds the worst case to linear
+//! time as well (introselect).
+//!
+//! Partial sorting works the same way, sorting only the partitions which
+//! overlap the requested ranks.
+
+use crate::heapsort::heapsort_gen;
+use crate::partition::partition3_gen;
+use crate::pdq::{insertion_sort, pdqsort_gen};
+use crate::pivot::{MedianOfThree, PivotStrategy};
+use crate::quicksort::depth_limit;
+use crate::{bounds, Comparator};
+use std::cmp::Ordering;
+use std::ops::{Bound, RangeBounds};
+
+/// Slices of up to this length are selected from by insertion sort.
+const MAX_INSERTION: usize = 10;
+/// Number of partitions keeping more than three quarters of a slice allowed
+/// before selection switches to the median of medians. Every other partition
+/// shrinks the slice geometrically, so a constant number of bad ones keeps
+/// selection linear.
+pub(crate) const MAX_BAD_PARTITIONS: usize = 16;
+
+/// Returns `true` if a partition of a slice of length `len` left a part of
+/// length `kept` to work on, too long to guarantee linear time.
+fn is_bad_partition(len: usize, kept: usize) -> bool {
+    kept > len - len / 4
+}
+
+/// Returns the index of an element close to the median of `arr`, found with
+/// the median of medians of groups of five. Rearranges `arr`.
+fn median_of_medians<T, F>(arr: &mut [T], compare: &mut F) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let groups = arr.len() / 5;
+    for group in 0..groups {
+        let start = group * 5;
+        insertion_sort(&mut arr[start..start + 5], compare, &mut ());
+        // Gather the medians at the front. Position `group` belongs to a group
+        // which was already handled.
+        arr.swap(group, start + 2);
+    }
+    let mid = groups / 2;
+    select_gen(&mut arr[..groups], mid, compare, 0);
+    mid
+}
+
+/// Moves the element of rank `k` to index `k` of `arr`, with no greater
+/// element before it and no smaller element after it. Partitions are pivoted
+/// on the median of three until `limit` of them were bad, see
+/// [`MAX_BAD_PARTITIONS`], then on the median of medians.
+pub(crate) fn select_gen<T, F>(mut arr: &mut [T], mut k: usize, compare: &mut F, mut limit: usize)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    loop {
+        if arr.len() <= MAX_INSERTION {
+            insertion_sort(arr, compare, &mut ());
+            return;
+        }
+
+        let len = arr.len();
+        let pivot = if limit == 0 {
+            median_of_medians(arr, compare)
+        } else {
+            MedianOfThree.select(arr, compare)
+        };
+
+        // Grouping the elements equal to the pivot keeps duplicates from
+        // unbalancing the partitions, and ends the search as soon as `k` falls
+        // among them.
+        let (lt, gt) = partition3_gen(arr, pivot, compare, &mut ());
+        if k < lt {
+            arr = &mut arr[..lt];
+        } else if k >= gt {
+            arr = &mut arr[gt..];
+            k -= gt;
+        } else {
+            return;
+        }
+        if is_bad_partition(len, arr.len()) {
+            limit = limit.saturating_sub(1);
+        }
+    }
+}
+
+/// Moves the element of every rank in `ranks`, which must be sorted, to the
+/// index of that rank, as [`select_gen`] does for a single rank. All ranks are
+/// selected in one pass: every partition is shared by the ranks on both of its
+/// sides, and sides without a requested rank are not looked at again.
+pub(crate) fn multi_select_gen<T, F>(arr: &mut [T], ranks: &[usize], compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut pending = vec![(0, arr.len(), ranks, MAX_BAD_PARTITIONS)];
+
+    while let Some((lo, hi, ranks, mut limit)) = pending.pop() {
+        let v = &mut arr[lo..hi];
+        match ranks {
+            [] => continue,
+            [k] => {
+                select_gen(v, k - lo, compare, limit);
+                continue;
+            }
+            _ if v.len() <= MAX_INSERTION => {
+                insertion_sort(v, compare, &mut ());
+                continue;
+            }
+            _ => {}
+        }
+
+        let pivot = if limit == 0 {
+            median_of_medians(v, compare)
+        } else {
+            MedianOfThree.select(v, compare)
+        };
+        let (lt, gt) = partition3_gen(v, pivot, compare, &mut ());
+        if is_bad_partition(v.len(), lt.max(v.len() - gt)) {
+            limit = limit.saturating_sub(1);
+        }
+        let (lt, gt) = (lo + lt, lo + gt);
+        let left = ranks.partition_point(|&k| k < lt);
+        let right = ranks.partition_point(|&k| k < gt);
+        pending.push((lo, lt, &ranks[..left], limit));
+        pending.push((gt, hi, &ranks[right..], limit));
+    }
+}
+
+/// Reorders a slice of i64 elements such that the element of rank `k` (the
+/// `k`-th smallest, counting from zero) is at index `k`, every element before
+/// it is not greater and every element after it is not smaller. Returns the
+/// element of rank `k`.
+///
+/// Runs in linear time, also in the worst case. Panics if `k` is out of bounds
+/// of the slice.
+pub fn select_nth(arr: &mut [i64], k: usize) -> &i64 {
+    select_nth_gen_by(arr, k, i64::cmp)
+}
+
+/// Reorders a slice of generic type such that the element of rank `k` is at
+/// index `k`, as [`select_nth`] does. Returns the element of rank `k`.
+///
+/// Panics if `k` is out of bounds of the slice.
+pub fn select_nth_gen<T: Comparator>(arr: &mut [T], k: usize) -> &T {
+    select_nth_gen_by(arr, k, T::compare)
+}
+
+/// Reorders a slice of generic type such that the element of rank `k` is at
+/// index `k`, using a comparator closure.
+///
+/// Panics if `k` is out of bounds of the slice.
+pub fn select_nth_gen_by<T, F>(arr: &mut [T], k: usize, mut compare: F) -> &T
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    assert!(
+        k < arr.len(),
+        "rank {} out of bounds of a slice of length {}",
+        k,
+        arr.len()
+    );
+    select_gen(arr, k, &mut compare, MAX_BAD_PARTITIONS);
+    &arr[k]
+}
+
+/// Sorts the elements of ranks `start..end` of `arr` into indices
+/// `start..end`, leaving smaller elements before them and greater elements
+/// after them in no particular order.
+///
+/// Partitions lying entirely outside the ranks are skipped, and partitions
+/// lying entirely inside them are sorted with pdqsort. Elements equal to the
+/// pivot are grouped around it and already in place, so duplicates do not
+/// unbalance the partitions. The smaller side of a partition is handled
+/// first, which bounds the stack to O(log n) ranges.
+fn sort_ranks_gen<T, F>(arr: &mut [T], start: usize, end: usize, compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut pending = vec![(0, arr.len(), depth_limit(arr.len()))];
+
+    while let Some((lo, hi, mut limit)) = pending.pop() {
+        if hi <= start || lo >= end || hi - lo <= 1 {
+            continue;
+        }
+        let v = &mut arr[lo..hi];
+        if start <= lo && hi <= end {
+            pdqsort_gen(v, compare, &mut ());
+            continue;
+        }
+        if limit == 0 {
+            heapsort_gen(v, compare, &mut ());
+            continue;
+        }
+        limit -= 1;
+
+        let pivot = MedianOfThree.select(v, compare);
+        let (lt, gt) = partition3_gen(v, pivot, compare, &mut ());
+        let (left, right) = ((lo, lo + lt), (lo + gt, hi));
+        let (smaller, larger) = if lt < hi - lo - gt {
+            (left, right)
+        } else {
+            (right, left)
+        };
+        pending.push((larger.0, larger.1, limit));
+        pending.push((smaller.0, smaller.1, limit));
+    }
+}
+
+/// Sorts the `k` smallest elements of a slice of generic type into its first
+/// `k` indices, leaving the other elements after them in no particular order.
+/// Returns the `k` sorted elements.
+///
+/// Takes O(n + k log k) time on average. Panics if `k` is greater than the
+/// length of the slice.
+pub fn partial_sort_gen<T: Comparator>(arr: &mut [T], k: usize) -> &[T] {
+    partial_sort_gen_by(arr, k, T::compare)
+}
+
+/// Sorts the `k` smallest elements of a slice of generic type into its first
+/// `k` indices, using a comparator closure.
+pub fn partial_sort_gen_by<T, F>(arr: &mut [T], k: usize, compare: F) -> &[T]
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    sort_range_of_ranks_by(arr, ..k, compare)
+}
+
+/// Puts the elements of the ranks in `range` of a slice of generic type at
+/// those indices, in sorted order. Elements of lower ranks end up before them
+/// and elements of higher ranks after them, in no particular order. Returns
+/// the sorted elements.
+///
+/// The result is the same sub-slice as sorting the whole slice and taking
+/// `range`, without sorting the elements outside of it. Panics if the range is
+/// out of bounds of the slice.
+pub fn sort_range_of_ranks<T, R>(arr: &mut [T], range: R) -> &[T]
+where
+    T: Comparator,
+    R: RangeBounds<usize>,
+{
+    sort_range_of_ranks_by(arr, range, T::compare)
+}
+
+/// Puts the elements of the ranks in `range` of a slice of generic type at
+/// those indices, in sorted order, using a comparator closure.
+pub fn sort_range_of_ranks_by<T, R, F>(arr: &mut [T], range: R, mut compare: F) -> &[T]
+where
+    R: RangeBounds<usize>,
+    F: FnMut(&T, &T) -> Ordering,
+{
+    // Indexing checks the range, after which its start cannot overflow.
+    let range = bounds(range);
+    let len = arr[range].len();
+    let start = match range.0 {
+        Bound::Included(start) => start,
+        Bound::Excluded(start) => start + 1,
+        Bound::Unbounded => 0,
+    };
+    let end = start + len;
+    sort_ranks_gen(arr, start, end, &mut compare);
+    &arr[start..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    fn check_selected(arr: &[i64], k: usize, expected: i64) {
+        assert_eq!(arr[k], expected);
+        assert!(arr[..k].iter().all(|&n| n <= expected));
+        assert!(arr[k + 1..].iter().all(|&n| n >= expected));
+    }
+
+    #[test]
+    fn test_select_nth() {
+        let mut rng = rand::thread_rng();
+        for &range in [5, 10_000].iter() {
+            let numbers: Vec<i64> = (0..1_000).map(|_| rng.gen_range(0..range)).collect();
+            let mut sorted = numbers.clone();
+            sorted.sort_unstable();
+
+            for &k in [0, 1, 499, 500, 990, 999].iter() {
+                let mut arr = numbers.clone();
+                assert_eq!(*select_nth(&mut arr, k), sorted[k]);
+                check_selected(&arr, k, sorted[k]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_median_of_medians_fallback() {
+        let mut rng = rand::thread_rng();
+        for len in [11, 57, 1_000].iter() {
+            let numbers: Vec<i64> = (0..*len).map(|_| rng.gen_range(0..100)).collect();
+            let mut sorted = numbers.clone();
+            sorted.sort_unstable();
+
+            let k = len / 3;
+            let mut arr = numbers;
+            select_gen(&mut arr, k, &mut i64::cmp, 0);
+            check_selected(&arr, k, sorted[k]);
+        }
+    }
+
+    #[test]
+    fn test_select_is_linear() {
+        let len = 100_000i64;
+        let patterns: Vec<Vec<i64>> = vec![
+            (0..len).collect(),
+            (0..len).rev().collect(),
+            (0..len).map(|n| n.min(len - n)).collect(),
+            (0..len).map(|n| n % 7).collect(),
+            vec![3; len as usize],
+        ];
+        for numbers in patterns {
+            let mut sorted = numbers.clone();
+            sorted.sort_unstable();
+            for &k in [0, len as usize / 3, len as usize - 1].iter() {
+                let mut arr = numbers.clone();
+                let mut compares = 0;
+                let selected = *select_nth_gen_by(&mut arr, k, |a: &i64, b: &i64| {
+                    compares += 1;
+                    a.cmp(b)
+                });
+                assert_eq!(selected, sorted[k]);
+                assert!(compares < 20 * len, "{} comparisons", compares);
+            }
+        }
+    }
+
+    #[test]
+    fn test_partial_sort_duplicates() {
+        // Two-way partitions only split off one element at a time here.
+        let len = 100_000;
+        let mut numbers = vec![7i64; len];
+        let mut compares = 0;
+        let sorted = partial_sort_gen_by(&mut numbers, len / 2, |a: &i64, b: &i64| {
+            compares += 1;
+            a.cmp(b)
+        });
+        assert_eq!(sorted.len(), len / 2);
+        assert!(compares < 3 * len, "{} comparisons", compares);
+    }
+
+    #[test]
+    fn test_select_nth_gen() {
+        let mut words = vec!["kobj", "systemd", "init", "kthreadd", "bash"];
+        assert_eq!(*select_nth_gen(&mut words, 2), "kobj");
+
+        let mut pids = vec![(4, "kobj2"), (2, "kobj"), (1, "systemd"), (3, "init")];
+        let (pid, name) = *select_nth_gen_by(&mut pids, 0, |a, b| b.0.cmp(&a.0));
+        assert_eq!((pid, name), (4, "kobj2"));
+    }
+
+    #[test]
+    fn test_multi_select() {
+        let mut rng = rand::thread_rng();
+        let numbers: Vec<i64> = (0..2_000).map(|_| rng.gen_range(0..500)).collect();
+        let mut sorted = numbers.clone();
+        sorted.sort_unstable();
+
+        let ranks = [0, 3, 3, 500, 1_000, 1_001, 1_999];
+        let mut arr = numbers;
+        multi_select_gen(&mut arr, &ranks, &mut i64::cmp);
+        for &k in ranks.iter() {
+            check_selected(&arr, k, sorted[k]);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_select_nth_out_of_bounds() {
+        select_nth(&mut [1, 2, 3], 3);
+    }
+
+    #[test]
+    fn test_partial_sort_gen() {
+        let mut rng = rand::thread_rng();
+        let numbers: Vec<i64> = (0..5_000).map(|_| rng.gen_range(0..1_000)).collect();
+        let mut sorted = numbers.clone();
+        sorted.sort_unstable();
+
+        for &k in [0, 1, 10, 2_500, 5_000].iter() {
+            let mut arr = numbers.clone();
+            assert_eq!(partial_sort_gen(&mut arr, k), &sorted[..k]);
+            if k > 0 && k < arr.len() {
+                assert!(arr[k..].iter().all(|&n| n >= arr[k - 1]));
+            }
+        }
+
+        let mut names = vec!["kobj", "systemd", "init", "kthreadd", "bash"];
+        assert_eq!(
+            partial_sort_gen_by(&mut names, 2, |a, b| b.cmp(a)),
+            &["systemd", "kthreadd"]
+        );
+    }
+
+    #[test]
+    fn test_sort_range_of_ranks() {
+        let mut rng = rand::thread_rng();
+        let numbers: Vec<i64> = (0..5_000).map(|_| rng.gen_range(0..1_000)).collect();
+        let mut sorted = numbers.clone();
+        sorted.sort_unstable();
+
+        let mut arr = numbers.clone();
+        assert_eq!(sort_range_of_ranks(&mut arr, 100..200), &sorted[100..200]);
+        assert!(arr[..100].iter().all(|&n| n <= sorted[100]));
+        assert!(arr[200..].iter().all(|&n| n >= sorted[199]));
+
+        let mut arr = numbers.clone();
+        assert_eq!(sort_range_of_ranks(&mut arr, 4_990..), &sorted[4_990..]);
+        let mut arr = numbers;
+        assert_eq!(sort_range_of_ranks(&mut arr, 7..=7), &sorted[7..=7]);
+        assert!(sort_range_of_ranks(&mut arr, 3..3).is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_partial_sort_out_of_bounds() {
+        partial_sort_gen(&mut [1, 2, 3], 4);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_sort_range_of_ranks_overflow() {
+        sort_range_of_ranks(&mut [1, 2, 3], ..=usize::MAX);
+    }
+}
@@ -0,0 +1,310 @@
+//! Configurable sorting through the [`Sorter`] builder.
+
+use crate::dual_pivot::dual_pivot_gen;
+use crate::pdq::pdqsort_gen;
+use crate::pivot::{MedianOfThree, PivotStrategy};
+use crate::quicksort::{depth_limit, quicksort_gen};
+use crate::stats::{Recorder, SortStats};
+use crate::Comparator;
+use std::cmp::Ordering;
+
+/// The sorting algorithm run by a [`Sorter`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Pattern-defeating quicksort. It adapts to presorted, reversed and
+    /// duplicate-heavy input on its own and is always O(n log n), so the
+    /// pivot strategy, introsort and three-way settings of the sorter are
+    /// ignored.
+    #[default]
+    Pdq,
+    /// Single-pivot quicksort, configured by the pivot strategy and the
+    /// partitioning scheme of the sorter.
+    Quicksort,
+    /// Yaroslavskiy's dual-pivot quicksort. It picks its own two pivots and
+    /// always partitions three ways, so the pivot strategy and three-way
+    /// setting of the sorter are ignored.
+    DualPivot,
+}
+
+/// A configurable sorter. The free functions of this crate use
+/// `Sorter::new()`, whose settings are the defaults described on each builder
+/// method.
+///
+/// A panicking comparator leaves the slice a permutation of its elements, see
+/// [`Comparator`].
+///
+/// None of the algorithms recurse: pending ranges live on an explicit stack of
+/// O(log n) entries, so sorting is safe on threads with small stacks.
+///
+/// ```
+/// use quicksort_gen::pivot::Ninther;
+/// use quicksort_gen::{Algorithm, Sorter};
+///
+/// let mut numbers = vec![3, 1, 2];
+/// Sorter::new()
+///     .algorithm(Algorithm::Quicksort)
+///     .pivot(Ninther)
+///     .sort(&mut numbers);
+/// assert_eq!(numbers, vec![1, 2, 3]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Sorter<P = MedianOfThree> {
+    pub(crate) algorithm: Algorithm,
+    pub(crate) pivot: P,
+    pub(crate) introsort: bool,
+    pub(crate) three_way: bool,
+    pub(crate) grain_size: usize,
+}
+
+/// Default number of elements below which parallel sorts stop splitting work
+/// across threads.
+pub(crate) const DEFAULT_GRAIN_SIZE: usize = 10_000;
+
+impl Sorter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Default for Sorter {
+    fn default() -> Self {
+        Sorter {
+            algorithm: Algorithm::Pdq,
+            pivot: MedianOfThree,
+            introsort: true,
+            three_way: false,
+            grain_size: DEFAULT_GRAIN_SIZE,
+        }
+    }
+}
+
+impl<P> Sorter<P> {
+    /// Sets the sorting algorithm, [`Algorithm::Pdq`] by default.
+    pub fn algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Sets the pivot selection strategy, [`MedianOfThree`] by default.
+    pub fn pivot<Q: PivotStrategy>(self, pivot: Q) -> Sorter<Q> {
+        Sorter {
+            algorithm: self.algorithm,
+            pivot,
+            introsort: self.introsort,
+            three_way: self.three_way,
+            grain_size: self.grain_size,
+        }
+    }
+
+    /// Enables introsort, on by default. Once partitioning gets deeper than
+    /// `2 * log2(n)` levels the remaining slice is heapsorted, which bounds
+    /// the sort to O(n log n) comparisons whatever the input and pivot
+    /// strategy. Disabling it gives plain quicksort.
+    pub fn introsort(mut self, enabled: bool) -> Self {
+        self.introsort = enabled;
+        self
+    }
+
+    /// Enables three-way partitioning, off by default. Elements equal to the
+    /// pivot are gathered in the middle of the partition and never looked at
+    /// again, which makes inputs with many duplicate keys sort in close to
+    /// linear time, at the cost of a few more swaps on distinct keys.
+    pub fn three_way(mut self, enabled: bool) -> Self {
+        self.three_way = enabled;
+        self
+    }
+
+    /// Sets the grain size of parallel sorts, 10 000 elements by default.
+    /// Slices up to this length are sorted on a single thread, since handing
+    /// them to another thread costs more than it saves. Clamped to at least 1.
+    pub fn grain_size(mut self, grain_size: usize) -> Self {
+        self.grain_size = grain_size.max(1);
+        self
+    }
+}
+
+impl<P: PivotStrategy + Clone> Sorter<P> {
+    /// Sorts i64 elements in a slice.
+    pub fn sort<'a>(&self, arr: &'a mut [i64]) -> &'a [i64] {
+        self.sort_gen_by(arr, i64::cmp)
+    }
+
+    /// Sorts a slice of generic type, which must define a comparator.
+    pub fn sort_gen<'a, T: Comparator>(&self, arr: &'a mut [T]) -> &'a [T] {
+        self.sort_gen_by(arr, T::compare)
+    }
+
+    /// Sorts a slice of generic type with a comparator closure.
+    pub fn sort_gen_by<'a, T, F>(&self, arr: &'a mut [T], mut compare: F) -> &'a [T]
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        self.run(arr, &mut compare, &mut ());
+        arr
+    }
+
+    /// Sorts i64 elements in a slice and returns the work done.
+    pub fn sort_stats(&self, arr: &mut [i64]) -> SortStats {
+        self.sort_gen_by_stats(arr, i64::cmp)
+    }
+
+    /// Sorts a slice of generic type, which must define a comparator, and
+    /// returns the work done.
+    pub fn sort_gen_stats<T: Comparator>(&self, arr: &mut [T]) -> SortStats {
+        self.sort_gen_by_stats(arr, T::compare)
+    }
+
+    /// Sorts a slice of generic type with a comparator closure and returns the
+    /// work done. Counting costs a little time, so measure with this and sort
+    /// with [`Sorter::sort_gen_by`].
+    ///
+    /// ```
+    /// use quicksort_gen::{Algorithm, Sorter};
+    ///
+    /// // Median of three pivots partition descending input badly, so
+    /// // quicksort ends up in heapsort, while pdqsort simply reverses it.
+    /// let mut numbers: Vec<i64> = (0..1_000).rev().collect();
+    /// let quicksort = Sorter::new()
+    ///     .algorithm(Algorithm::Quicksort)
+    ///     .sort_gen_by_stats(&mut numbers, i64::cmp);
+    /// assert!(quicksort.fallbacks > 0);
+    ///
+    /// numbers.reverse();
+    /// let pdq = Sorter::new().sort_gen_by_stats(&mut numbers, i64::cmp);
+    /// assert_eq!(pdq.fallbacks, 0);
+    /// assert!(pdq.comparisons < quicksort.comparisons);
+    /// ```
+    pub fn sort_gen_by_stats<T, F>(&self, arr: &mut [T], mut compare: F) -> SortStats
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        let mut stats = SortStats::default();
+        let mut comparisons = 0;
+        let mut counted = |a: &T, b: &T| {
+            comparisons += 1;
+            compare(a, b)
+        };
+        self.run(arr, &mut counted, &mut stats);
+        stats.comparisons = comparisons;
+        stats
+    }
+
+    fn run<T, F, S>(&self, arr: &mut [T], compare: &mut F, stats: &mut S)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+        S: Recorder,
+    {
+        let limit = if self.introsort {
+            depth_limit(arr.len())
+        } else {
+            usize::MAX
+        };
+        match self.algorithm {
+            Algorithm::Quicksort => {
+                // Every sort starts from the configured strategy, so a seeded
+                // random pivot picks the same pivots on every call.
+                let mut pivot = self.pivot.clone();
+                quicksort_gen(arr, &mut pivot, compare, limit, self.three_way, stats);
+            }
+            Algorithm::Pdq => pdqsort_gen(arr, compare, stats),
+            Algorithm::DualPivot => dual_pivot_gen(arr, compare, limit, stats),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pivot::{First, Last, Ninther, Random};
+    use rand::Rng;
+
+    fn check<P: PivotStrategy + Clone>(sorter: Sorter<P>) {
+        let mut rng = rand::thread_rng();
+        let mut numbers: Vec<i64> = (0..500).map(|_| rng.gen_range(-100..100)).collect();
+        let mut expected = numbers.clone();
+        expected.sort_unstable();
+        assert_eq!(sorter.sort(&mut numbers), &expected[..]);
+
+        let mut words: Vec<String> = numbers.iter().rev().map(|n| n.to_string()).collect();
+        let mut expected: Vec<String> = words.clone();
+        expected.sort_unstable();
+        assert_eq!(sorter.sort_gen(&mut words), &expected[..]);
+    }
+
+    #[test]
+    fn test_algorithms() {
+        check(Sorter::new());
+        check(Sorter::new().algorithm(Algorithm::DualPivot));
+        check(
+            Sorter::new()
+                .algorithm(Algorithm::DualPivot)
+                .introsort(false),
+        );
+    }
+
+    #[test]
+    fn test_pivot_strategies() {
+        let quicksort = Sorter::new().algorithm(Algorithm::Quicksort);
+        check(quicksort.clone());
+        check(quicksort.clone().pivot(First));
+        check(quicksort.clone().pivot(Last));
+        check(quicksort.clone().pivot(Ninther));
+        check(quicksort.clone().pivot(Random::new()));
+        check(quicksort.clone().pivot(Random::seeded(42)));
+        check(quicksort.clone().introsort(false));
+        check(quicksort.clone().pivot(Last).introsort(false));
+        check(quicksort.clone().three_way(true));
+        check(quicksort.pivot(Random::seeded(3)).three_way(true));
+    }
+
+    #[test]
+    fn test_presorted_input() {
+        let quicksort = Sorter::new().algorithm(Algorithm::Quicksort);
+        let mut numbers: Vec<i64> = (0..100_000).collect();
+        quicksort.sort(&mut numbers);
+        assert!(numbers.windows(2).all(|w| w[0] <= w[1]));
+
+        numbers.reverse();
+        quicksort.clone().pivot(Ninther).sort(&mut numbers);
+        assert!(numbers.windows(2).all(|w| w[0] <= w[1]));
+
+        // Quadratic for plain quicksort, but introsort falls back to heapsort.
+        quicksort.pivot(Last).sort(&mut numbers);
+        assert!(numbers.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn test_sort_stats() {
+        let mut rng = rand::thread_rng();
+        let mut numbers: Vec<i64> = (0..10_000).map(|_| rng.gen()).collect();
+        for algorithm in [Algorithm::Pdq, Algorithm::Quicksort, Algorithm::DualPivot] {
+            numbers.reverse();
+            let stats = Sorter::new().algorithm(algorithm).sort_stats(&mut numbers);
+            assert!(numbers.windows(2).all(|w| w[0] <= w[1]));
+            assert!(
+                stats.comparisons >= 10_000 && stats.swaps > 0,
+                "{:?}",
+                stats
+            );
+            assert!(stats.partitions > 0 && stats.max_depth > 0, "{:?}", stats);
+            assert!(stats.max_depth <= depth_limit(10_000), "{:?}", stats);
+            assert_eq!(stats.copies, 0);
+        }
+
+        // Sorted input with the last element as pivot exhausts the depth limit.
+        let stats = Sorter::new()
+            .algorithm(Algorithm::Quicksort)
+            .pivot(Last)
+            .sort_stats(&mut numbers);
+        assert!(stats.fallbacks > 0);
+        assert_eq!(stats.max_depth, depth_limit(10_000));
+
+        let mut words = vec!["b", "a"];
+        let stats = Sorter::new().sort_gen_stats(&mut words);
+        assert_eq!(words, vec!["a", "b"]);
+        assert_eq!(
+            (stats.comparisons, stats.swaps, stats.partitions),
+            (1, 1, 0)
+        );
+    }
+}
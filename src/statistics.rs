@@ -0,0 +1,169 @@
+//! Order statistics: medians, quantiles and interquartile ranges.
+//!
+//! These only partition the slice as far as needed to put the requested ranks
+//! in place, which takes linear time in the worst case instead of the
+//! O(n log n) of sorting it. Several quantiles are computed in a single pass,
+//! in O(n log q) time for `q` quantiles. The slices are reordered.
+//!
+//! Quantiles of `i64` slices interpolate linearly between the two closest
+//! ranks, as most statistics packages do. Generic types cannot be
+//! interpolated, so their quantile `q` of `n` elements is the element of rank
+//! `floor(q * (n - 1))`, the lower of those two ranks.
+
+use crate::select::multi_select_gen;
+use crate::Comparator;
+
+/// Returns the (fractional) position of quantile `q` in a sorted slice of
+/// length `len`.
+fn position(q: f64, len: usize) -> f64 {
+    assert!(
+        (0.0..=1.0).contains(&q),
+        "quantile {} is not between 0 and 1",
+        q
+    );
+    q * (len - 1) as f64
+}
+
+/// Returns the median of a slice of i64 elements, the mean of the two middle
+/// elements for even lengths, or `None` if the slice is empty.
+pub fn median(arr: &mut [i64]) -> Option<f64> {
+    quantile(arr, 0.5)
+}
+
+/// Returns quantile `q` of a slice of i64 elements, or `None` if the slice is
+/// empty. Panics unless `0 <= q <= 1`.
+pub fn quantile(arr: &mut [i64], q: f64) -> Option<f64> {
+    quantiles(arr, &[q]).map(|values| values[0])
+}
+
+/// Returns every quantile of `qs` for a slice of i64 elements, in the same
+/// order, or `None` if the slice is empty. Panics unless every quantile is
+/// between 0 and 1.
+pub fn quantiles(arr: &mut [i64], qs: &[f64]) -> Option<Vec<f64>> {
+    if arr.is_empty() {
+        return None;
+    }
+
+    let positions: Vec<f64> = qs.iter().map(|&q| position(q, arr.len())).collect();
+    let mut ranks: Vec<usize> = positions
+        .iter()
+        .flat_map(|pos| [pos.floor() as usize, pos.ceil() as usize])
+        .collect();
+    ranks.sort_unstable();
+    ranks.dedup();
+    multi_select_gen(arr, &ranks, &mut i64::cmp);
+
+    let values = positions
+        .iter()
+        .map(|pos| {
+            let lower = arr[pos.floor() as usize] as f64;
+            let upper = arr[pos.ceil() as usize] as f64;
+            lower + (upper - lower) * pos.fract()
+        })
+        .collect();
+    Some(values)
+}
+
+/// Returns the interquartile range of a slice of i64 elements, the distance
+/// between its first and third quartiles, or `None` if the slice is empty.
+pub fn interquartile_range(arr: &mut [i64]) -> Option<f64> {
+    quantiles(arr, &[0.25, 0.75]).map(|values| values[1] - values[0])
+}
+
+/// Returns the lower median of a slice of generic type, or `None` if the
+/// slice is empty.
+pub fn median_gen<T: Comparator>(arr: &mut [T]) -> Option<&T> {
+    quantile_gen(arr, 0.5)
+}
+
+/// Returns quantile `q` of a slice of generic type, or `None` if the slice is
+/// empty. Panics unless `0 <= q <= 1`.
+pub fn quantile_gen<T: Comparator>(arr: &mut [T], q: f64) -> Option<&T> {
+    quantiles_gen(arr, &[q]).map(|values| values[0])
+}
+
+/// Returns every quantile of `qs` for a slice of generic type, in the same
+/// order, or `None` if the slice is empty. Panics unless every quantile is
+/// between 0 and 1.
+pub fn quantiles_gen<'a, T: Comparator>(arr: &'a mut [T], qs: &[f64]) -> Option<Vec<&'a T>> {
+    if arr.is_empty() {
+        return None;
+    }
+
+    let requested: Vec<usize> = qs
+        .iter()
+        .map(|&q| position(q, arr.len()).floor() as usize)
+        .collect();
+    let mut ranks = requested.clone();
+    ranks.sort_unstable();
+    ranks.dedup();
+    multi_select_gen(arr, &ranks, &mut T::compare);
+
+    let arr: &'a [T] = arr;
+    Some(requested.iter().map(|&rank| &arr[rank]).collect())
+}
+
+/// Returns the first and third quartiles of a slice of generic type, or
+/// `None` if the slice is empty.
+pub fn interquartile_range_gen<T: Comparator>(arr: &mut [T]) -> Option<(&T, &T)> {
+    quantiles_gen(arr, &[0.25, 0.75]).map(|values| (values[0], values[1]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(&mut [3, 1, 2]), Some(2.0));
+        assert_eq!(median(&mut [4, 1, 3, 2]), Some(2.5));
+        assert_eq!(median(&mut [7]), Some(7.0));
+        assert_eq!(median(&mut []), None);
+    }
+
+    #[test]
+    fn test_quantiles() {
+        let mut latencies: Vec<i64> = (1..=101).rev().collect();
+        let values = quantiles(&mut latencies, &[0.99, 0.5, 0.0, 1.0, 0.125]).unwrap();
+        assert_eq!(values, vec![100.0, 51.0, 1.0, 101.0, 13.5]);
+        assert_eq!(interquartile_range(&mut latencies), Some(50.0));
+    }
+
+    #[test]
+    fn test_quantiles_random() {
+        let mut rng = rand::thread_rng();
+        let mut numbers: Vec<i64> = (0..1_000).map(|_| rng.gen_range(0..100)).collect();
+        let mut sorted = numbers.clone();
+        sorted.sort_unstable();
+
+        let values = quantiles(&mut numbers, &[0.5, 0.9, 0.999]).unwrap();
+        assert_eq!(values[0], (sorted[499] + sorted[500]) as f64 / 2.0);
+        let expected = sorted[899] as f64 + (sorted[900] - sorted[899]) as f64 * 0.1;
+        assert!((values[1] - expected).abs() < 1e-9);
+        assert!(values[2] >= sorted[998] as f64 && values[2] <= sorted[999] as f64);
+    }
+
+    #[test]
+    fn test_quantiles_gen() {
+        let mut names = vec!["kobj", "systemd", "init", "kthreadd", "bash", "sshd"];
+        assert_eq!(median_gen(&mut names), Some(&"kobj"));
+        assert_eq!(
+            quantiles_gen(&mut names, &[1.0, 0.0]),
+            Some(vec![&"systemd", &"bash"])
+        );
+        assert_eq!(
+            interquartile_range_gen(&mut names),
+            Some((&"init", &"kthreadd"))
+        );
+
+        let mut empty: Vec<String> = Vec::new();
+        assert_eq!(median_gen(&mut empty), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_quantile_out_of_range() {
+        quantile(&mut [1, 2, 3], 1.5);
+    }
+}
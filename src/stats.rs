@@ -0,0 +1,66 @@
+//! Measurements of the work done by a sort.
+
+/// Counters of the work done by one sort, as returned by
+/// [`Sorter::sort_gen_by_stats`](crate::Sorter::sort_gen_by_stats) and the
+/// other `_stats` variants of the sorting functions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SortStats {
+    /// Calls of the comparator.
+    pub comparisons: u64,
+    /// Swaps of two distinct elements. Swapping an element with itself does
+    /// not move anything and is not counted.
+    pub swaps: u64,
+    /// Reserved for elements copied with `Copier`. Every engine only swaps
+    /// elements, so this is always zero for now. It is kept so that an engine
+    /// which copies can be added without changing the fields of the report.
+    pub copies: u64,
+    /// Deepest level of partitioning reached, 0 if the slice was never
+    /// partitioned.
+    pub max_depth: usize,
+    /// Partitioning passes, each splitting a range around its pivots.
+    pub partitions: u64,
+    /// Ranges handed to heapsort, once partitioning went too deep or was too
+    /// often unbalanced.
+    pub fallbacks: u64,
+}
+
+/// Receives the events of a sort. Engines take a recorder so the same code
+/// serves instrumented sorts, which record into [`SortStats`], and plain
+/// sorts, which pass `()` and pay nothing.
+pub(crate) trait Recorder {
+    fn swap<T>(&mut self, arr: &mut [T], a: usize, b: usize) {
+        arr.swap(a, b);
+    }
+
+    fn reverse<T>(&mut self, arr: &mut [T]) {
+        arr.reverse();
+    }
+
+    /// Records a partition of a range `depth` levels deep, starting from 1.
+    fn partition(&mut self, _depth: usize) {}
+
+    fn fallback(&mut self) {}
+}
+
+impl Recorder for () {}
+
+impl Recorder for SortStats {
+    fn swap<T>(&mut self, arr: &mut [T], a: usize, b: usize) {
+        self.swaps += (a != b) as u64;
+        arr.swap(a, b);
+    }
+
+    fn reverse<T>(&mut self, arr: &mut [T]) {
+        self.swaps += (arr.len() / 2) as u64;
+        arr.reverse();
+    }
+
+    fn partition(&mut self, depth: usize) {
+        self.partitions += 1;
+        self.max_depth = self.max_depth.max(depth);
+    }
+
+    fn fallback(&mut self) {
+        self.fallbacks += 1;
+    }
+}
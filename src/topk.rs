@@ -0,0 +1,118 @@
+//! Streaming selection of the largest elements of an iterator.
+
+use crate::select::{select_gen, MAX_BAD_PARTITIONS};
+use crate::{sort_gen, Comparator};
+
+/// Accumulates the `k` largest elements pushed into it, in O(k) memory.
+///
+/// Elements are buffered until the buffer holds `2 * k` of them. The buffer is
+/// then cut down to its `k` largest elements with quickselect, so every element
+/// costs amortized constant time however long the stream is.
+///
+/// ```
+/// use quicksort_gen::TopK;
+///
+/// let mut top = TopK::new(3);
+/// top.extend(vec![5, 1, 9, 3, 7, 2]);
+/// assert_eq!(top.into_sorted_vec(), vec![5, 7, 9]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct TopK<T> {
+    k: usize,
+    buffer: Vec<T>,
+}
+
+impl<T: Comparator> TopK<T> {
+    /// Creates an accumulator keeping the `k` largest elements. Nothing is
+    /// allocated up front, so a large `k` costs nothing on a short stream.
+    pub fn new(k: usize) -> Self {
+        Self {
+            k,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Offers an element to the accumulator.
+    pub fn push(&mut self, elem: T) {
+        if self.k == 0 {
+            return;
+        }
+        if self.buffer.len() == self.k.saturating_mul(2) {
+            self.prune();
+        }
+        self.buffer.push(elem);
+    }
+
+    /// Keeps only the `k` largest buffered elements.
+    fn prune(&mut self) {
+        let len = self.buffer.len();
+        if len <= self.k {
+            return;
+        }
+        let rank = len - self.k;
+        select_gen(&mut self.buffer, rank, &mut T::compare, MAX_BAD_PARTITIONS);
+        self.buffer.drain(..rank);
+    }
+
+    /// Returns the `k` largest elements pushed so far in ascending order, so
+    /// the largest element is last. Returns fewer elements if fewer than `k`
+    /// were pushed.
+    pub fn into_sorted_vec(mut self) -> Vec<T> {
+        self.prune();
+        sort_gen(&mut self.buffer);
+        self.buffer
+    }
+}
+
+impl<T: Comparator> Extend<T> for TopK<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for elem in iter {
+            self.push(elem);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn test_top_k() {
+        let mut rng = rand::thread_rng();
+        let numbers: Vec<i64> = (0..10_000).map(|_| rng.gen_range(0..1_000)).collect();
+        let mut sorted = numbers.clone();
+        sorted.sort_unstable();
+
+        for &k in [1, 7, 100].iter() {
+            let mut top = TopK::new(k);
+            top.extend(numbers.iter().copied());
+            assert_eq!(top.into_sorted_vec(), &sorted[sorted.len() - k..]);
+        }
+    }
+
+    #[test]
+    fn test_top_k_short_stream() {
+        let mut top = TopK::new(5);
+        top.extend(vec!["kobj", "systemd", "init"]);
+        assert_eq!(top.into_sorted_vec(), vec!["init", "kobj", "systemd"]);
+
+        let mut none = TopK::new(0);
+        none.extend(0..100);
+        assert!(none.into_sorted_vec().is_empty());
+
+        let mut all = TopK::new(usize::MAX);
+        all.extend((0..100).rev());
+        assert_eq!(all.into_sorted_vec(), (0..100).collect::<Vec<i32>>());
+    }
+
+    #[test]
+    fn test_top_k_bounded_buffer() {
+        let mut top = TopK::new(10);
+        for n in 0..1_000 {
+            top.push(n);
+            assert!(top.buffer.len() <= 20);
+        }
+        assert_eq!(top.into_sorted_vec(), (990..1_000).collect::<Vec<i32>>());
+    }
+}
@@ -0,0 +1,164 @@
+//! Verification of sorted output.
+
+use crate::{sort_gen_by, Comparator};
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+
+/// The reason a slice is not a sorted permutation of another.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VerifyError {
+    /// The slices have different lengths.
+    LengthMismatch { original: usize, sorted: usize },
+    /// `sorted[index]` is greater than `sorted[index + 1]`.
+    Unordered { index: usize },
+    /// The slices hold different elements: the element of rank `index` in the
+    /// original slice does not compare equal to `sorted[index]`.
+    NotPermutation { index: usize },
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::LengthMismatch { original, sorted } => write!(
+                f,
+                "sorted slice has {} elements but the original has {}",
+                sorted, original
+            ),
+            VerifyError::Unordered { index } => write!(
+                f,
+                "elements at {} and {} are out of order",
+                index,
+                index + 1
+            ),
+            VerifyError::NotPermutation { index } => write!(
+                f,
+                "element at {} differs from the element of the same rank in the original",
+                index
+            ),
+        }
+    }
+}
+
+impl Error for VerifyError {}
+
+/// Returns `true` if a slice of generic type is sorted in ascending order.
+/// Equal adjacent elements are allowed.
+pub fn is_sorted_gen<T: Comparator>(arr: &[T]) -> bool {
+    is_sorted_by(arr, T::compare)
+}
+
+/// Returns `true` if a slice of generic type is sorted according to a
+/// comparator closure. Equal adjacent elements are allowed.
+pub fn is_sorted_by<T, F>(arr: &[T], compare: F) -> bool
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    first_unordered(arr, compare).is_none()
+}
+
+/// Returns the first index whose element is greater than the next one.
+fn first_unordered<T, F>(arr: &[T], mut compare: F) -> Option<usize>
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    arr.windows(2)
+        .position(|pair| compare(&pair[0], &pair[1]) == Ordering::Greater)
+}
+
+/// Checks that `sorted` is in ascending order and holds the same elements as
+/// `original`, as any sort of `original` must.
+///
+/// Elements are told apart with their comparator only, so elements which
+/// compare equal are treated as interchangeable. `original` is not modified:
+/// its elements are ranked through a vector of references.
+pub fn verify_sorted_permutation<T: Comparator>(
+    original: &[T],
+    sorted: &[T],
+) -> Result<(), VerifyError> {
+    verify_sorted_permutation_by(original, sorted, T::compare)
+}
+
+/// Checks that `sorted` is sorted according to a comparator closure and holds
+/// the same elements as `original`. See [`verify_sorted_permutation`].
+pub fn verify_sorted_permutation_by<T, F>(
+    original: &[T],
+    sorted: &[T],
+    mut compare: F,
+) -> Result<(), VerifyError>
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    if original.len() != sorted.len() {
+        return Err(VerifyError::LengthMismatch {
+            original: original.len(),
+            sorted: sorted.len(),
+        });
+    }
+    if let Some(index) = first_unordered(sorted, &mut compare) {
+        return Err(VerifyError::Unordered { index });
+    }
+
+    let mut ranked: Vec<&T> = original.iter().collect();
+    sort_gen_by(&mut ranked, |a, b| compare(a, b));
+    match ranked
+        .iter()
+        .zip(sorted)
+        .position(|(&a, b)| compare(a, b) != Ordering::Equal)
+    {
+        Some(index) => Err(VerifyError::NotPermutation { index }),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_sorted() {
+        assert!(is_sorted_gen::<i64>(&[]));
+        assert!(is_sorted_gen(&[1]));
+        assert!(is_sorted_gen(&[1, 2, 2, 3]));
+        assert!(!is_sorted_gen(&[1, 3, 2]));
+        assert!(is_sorted_by(&[3, 2, 2, 1], |a: &i64, b| b.cmp(a)));
+        assert!(!is_sorted_by(&["bb", "a", "ccc"], |a, b| a
+            .len()
+            .cmp(&b.len())));
+    }
+
+    #[test]
+    fn test_verify_sorted_permutation() {
+        let original = [3, 1, 2, 1];
+        assert_eq!(verify_sorted_permutation(&original, &[1, 1, 2, 3]), Ok(()));
+        assert_eq!(
+            verify_sorted_permutation(&original, &[1, 2, 3]),
+            Err(VerifyError::LengthMismatch {
+                original: 4,
+                sorted: 3
+            })
+        );
+        assert_eq!(
+            verify_sorted_permutation(&original, &[1, 2, 1, 3]),
+            Err(VerifyError::Unordered { index: 1 })
+        );
+        assert_eq!(
+            verify_sorted_permutation(&original, &[1, 2, 2, 3]),
+            Err(VerifyError::NotPermutation { index: 1 })
+        );
+    }
+
+    #[test]
+    fn test_verify_sorted_permutation_by() {
+        let original = ["kobj", "systemd", "init"];
+        let sorted = ["systemd", "kobj", "init"];
+        assert_eq!(
+            verify_sorted_permutation_by(&original, &sorted, |a, b| b.cmp(a)),
+            Ok(())
+        );
+        assert_eq!(
+            VerifyError::Unordered { index: 4 }.to_string(),
+            "elements at 4 and 5 are out of order"
+        );
+    }
+}